@@ -0,0 +1,242 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
+};
+
+use jl_proc::LineItem;
+
+// --------------------------------------------------------------------------
+
+/// How long to wait before checking a file again at its end.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A reader that keeps reading a file as it grows, like `tail -F`.
+///
+/// At the end of the file, the reader waits for more data instead of ending.
+/// When the file at the path is replaced (log rotation), the rest of the old
+/// file is read before switching to the new one, and when the file shrinks
+/// (truncation), reading starts over from the beginning. Both are reported
+/// as notices.
+pub struct FollowReader {
+    path: PathBuf,
+    file: File,
+    id: Option<FileId>,
+    position: u64,
+    poll_interval: Duration,
+    notices: Notices,
+}
+
+impl FollowReader {
+    /// Opens the file at the path for following, starting at the beginning.
+    pub fn open(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let file = File::open(&path)?;
+        Ok(Self {
+            id: file_id(&file.metadata()?),
+            path,
+            file,
+            position: 0,
+            poll_interval: POLL_INTERVAL,
+            notices: Notices::default(),
+        })
+    }
+
+    /// Reports rotation and truncation to the notices.
+    pub fn with_notices(mut self, notices: Notices) -> Self {
+        self.notices = notices;
+        self
+    }
+
+    /// Starts reading at the given position instead of the beginning.
+    pub fn seek_to(&mut self, position: u64) -> std::io::Result<()> {
+        self.position = self.file.seek(SeekFrom::Start(position))?;
+        Ok(())
+    }
+
+    /// Checks whether the file was rotated or truncated, and reopens or
+    /// rewinds it if so. Returns true if there may be more to read.
+    fn check_file(&mut self) -> std::io::Result<bool> {
+        // a missing file is in the middle of being rotated, so we wait
+        let Ok(metadata) = std::fs::metadata(&self.path) else {
+            return Ok(false);
+        };
+        if file_id(&metadata) != self.id {
+            self.notices.push("File replaced, reopening");
+            self.file = File::open(&self.path)?;
+            self.id = file_id(&self.file.metadata()?);
+            self.position = 0;
+            return Ok(true);
+        }
+        if metadata.len() < self.position {
+            self.notices.push("File truncated, reading from the start");
+            self.seek_to(0)?;
+            return Ok(true);
+        }
+        Ok(metadata.len() > self.position)
+    }
+}
+
+impl Read for FollowReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = self.file.read(buf)?;
+            if n > 0 {
+                self.position += n as u64;
+                return Ok(n);
+            }
+            if !self.check_file()? {
+                std::thread::sleep(self.poll_interval);
+            }
+        }
+    }
+}
+
+/// Notices about a followed file, kept by its reader until they are shown
+/// with the items read from it.
+#[derive(Debug, Clone, Default)]
+pub struct Notices(Rc<RefCell<Vec<String>>>);
+
+impl Notices {
+    fn push(&self, notice: &str) {
+        self.0.borrow_mut().push(notice.into());
+    }
+
+    /// Puts the notices in front of the items read after them.
+    pub fn interleave<'a>(
+        self,
+        mut items: impl Iterator<Item = LineItem> + 'a,
+    ) -> impl Iterator<Item = LineItem> + 'a {
+        let mut pending = VecDeque::new();
+        std::iter::from_fn(move || {
+            if pending.is_empty() {
+                let item = items.next()?;
+                pending.extend(self.0.borrow_mut().drain(..).map(LineItem::Notice));
+                pending.push_back(item);
+            }
+            pending.pop_front()
+        })
+    }
+}
+
+/// Identifies a file independently of its path, to notice when the file at
+/// a path is replaced.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileId {
+    device: u64,
+    inode: u64,
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some(FileId {
+        device: metadata.dev(),
+        inode: metadata.ino(),
+    })
+}
+
+/// Without inodes, only truncation is noticed.
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<FileId> {
+    None
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+
+    use super::*;
+
+    fn temp_log(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("jl-cat-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        std::fs::write(&path, "first line\n").unwrap();
+        (dir, path)
+    }
+
+    fn follow(path: &PathBuf) -> BufReader<FollowReader> {
+        let mut reader = FollowReader::open(path).unwrap();
+        reader.poll_interval = Duration::from_millis(1);
+        BufReader::new(reader)
+    }
+
+    fn read_line(reader: &mut impl BufRead) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line
+    }
+
+    #[test]
+    fn starts_over_after_truncation() {
+        let (dir, path) = temp_log("truncate");
+        let mut reader = follow(&path);
+        assert_eq!(read_line(&mut reader), "first line\n");
+        std::fs::write(&path, "2\n").unwrap();
+        assert_eq!(read_line(&mut reader), "2\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shows_notices_before_the_items_after_them() {
+        let (dir, path) = temp_log("notices");
+        let notices = Notices::default();
+        let mut reader = FollowReader::open(&path)
+            .unwrap()
+            .with_notices(notices.clone());
+        reader.poll_interval = Duration::from_millis(1);
+        let lines = BufReader::new(reader)
+            .lines()
+            .map(|line| LineItem::Text(0, line.unwrap()));
+        let mut items = notices.interleave(lines).map(|item| match item {
+            LineItem::Text(_, text) => text,
+            LineItem::Notice(notice) => format!("notice: {notice}"),
+            _ => "other".into(),
+        });
+        assert_eq!(items.next().unwrap(), "first line");
+        std::fs::write(&path, "2\n").unwrap();
+        assert_eq!(
+            items.next().unwrap(),
+            "notice: File truncated, reading from the start"
+        );
+        assert_eq!(items.next().unwrap(), "2");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_start_at_a_position() {
+        let (dir, path) = temp_log("seek");
+        let mut reader = FollowReader::open(&path).unwrap();
+        reader.seek_to(6).unwrap();
+        assert_eq!(read_line(&mut BufReader::new(reader)), "line\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reopens_rotated_files() {
+        let (dir, path) = temp_log("rotate");
+        let mut reader = follow(&path);
+        assert_eq!(read_line(&mut reader), "first line\n");
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"2\n").unwrap();
+        std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+        std::fs::write(&path, "3\n").unwrap();
+        assert_eq!(read_line(&mut reader), "2\n");
+        assert_eq!(read_line(&mut reader), "3\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use bzip2::read::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+
+use crate::{
+    follow::{FollowReader, Notices},
+    seek::search,
+    tail::tail_offset,
+};
+
+// --------------------------------------------------------------------------
+
+/// An input to read log entries from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    /// Returns the name of the input, as shown in headers and errors.
+    pub fn name(&self) -> &str {
+        match self {
+            Input::Stdin => "<STDIN>",
+            Input::File(path) => path.to_str().unwrap_or("<n/a>"),
+        }
+    }
+
+    /// Returns the file name of the input, like `api.log.1` for
+    /// `/var/log/api.log.1`.
+    pub fn file_name(&self) -> &str {
+        match self {
+            Input::Stdin => "stdin",
+            Input::File(path) => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_else(|| self.name()),
+        }
+    }
+
+    /// Returns a short label for the input, the file name up to the first
+    /// dot, like `api` for `/var/log/api.log.1.gz`.
+    pub fn label(&self) -> &str {
+        match self {
+            Input::Stdin => "stdin",
+            Input::File(path) => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.split('.').find(|part| !part.is_empty()))
+                .unwrap_or_else(|| self.name()),
+        }
+    }
+
+    /// Opens the input for reading, decompressing it if needed.
+    pub fn open(&self) -> anyhow::Result<Box<dyn BufRead>> {
+        Ok(match self {
+            Input::Stdin => decompressed(std::io::stdin().lock())?,
+            Input::File(path) => {
+                let file =
+                    File::open(path).with_context(|| format!("can't open '{}'", path.display()))?;
+                decompressed(file)?
+            }
+        })
+    }
+}
+
+impl Input {
+    /// Opens the input for following as it grows, reporting rotation and
+    /// truncation to the notices. Standard input is read as usual, as it
+    /// only ends when the writer is done.
+    pub fn follow(&self, notices: &Notices) -> anyhow::Result<Box<dyn BufRead>> {
+        Ok(match self {
+            Input::Stdin => Box::new(std::io::stdin().lock()),
+            Input::File(path) => {
+                let reader = FollowReader::open(path)
+                    .with_context(|| format!("can't open '{}'", path.display()))?
+                    .with_notices(notices.clone());
+                Box::new(BufReader::new(reader))
+            }
+        })
+    }
+}
+
+impl Input {
+    /// Opens the input at the start of its last `lines` lines, found by
+    /// reading backwards from the end, and follows it if given notices to
+    /// report to. Returns `None` for inputs that can't be read from the end,
+    /// like standard input and compressed files.
+    pub fn open_tail(
+        &self,
+        lines: usize,
+        follow: Option<&Notices>,
+    ) -> anyhow::Result<Option<Box<dyn BufRead>>> {
+        let Some(mut file) = self.open_seekable()? else {
+            return Ok(None);
+        };
+        let offset = tail_offset(&mut file, lines)?;
+        self.open_at(file, offset, follow).map(Some)
+    }
+
+    /// Opens the input close before the first entry for which `is_before` is
+    /// false, found by a binary search, and follows it like `open_tail`. Returns
+    /// the reader and the offset it starts at, or `None` for inputs that
+    /// can't be searched, like standard input, compressed files and files
+    /// that turn out not to be sorted by timestamp.
+    pub fn open_search(
+        &self,
+        is_before: impl Fn(jiff::Timestamp) -> bool,
+        timestamp_of: impl Fn(&str) -> Option<jiff::Timestamp>,
+        follow: Option<&Notices>,
+    ) -> anyhow::Result<Option<(Box<dyn BufRead>, u64)>> {
+        let Some(mut file) = self.open_seekable()? else {
+            return Ok(None);
+        };
+        let Some(offset) = search(&mut file, is_before, timestamp_of)? else {
+            return Ok(None);
+        };
+        Ok(Some((self.open_at(file, offset, follow)?, offset)))
+    }
+
+    /// Returns true if the input can be read from any position, like for
+    /// `open_tail`.
+    pub fn is_seekable(&self) -> anyhow::Result<bool> {
+        Ok(self.open_seekable()?.is_some())
+    }
+
+    /// Opens the input as a file that can be read from any position, if it
+    /// is a regular file that isn't compressed.
+    fn open_seekable(&self) -> anyhow::Result<Option<File>> {
+        let Input::File(path) = self else {
+            return Ok(None);
+        };
+        let mut file =
+            File::open(path).with_context(|| format!("can't open '{}'", path.display()))?;
+        // bytes read from pipes and devices would be lost for the reader
+        // opened after this
+        if !file.metadata()?.is_file() {
+            return Ok(None);
+        }
+        let mut start = Vec::new();
+        file.by_ref().take(4).read_to_end(&mut start)?;
+        if Compression::detect(&start).is_some() {
+            return Ok(None);
+        }
+        Ok(Some(file))
+    }
+
+    /// Starts reading a file opened with `open_seekable` at the offset.
+    fn open_at(
+        &self,
+        mut file: File,
+        offset: u64,
+        follow: Option<&Notices>,
+    ) -> anyhow::Result<Box<dyn BufRead>> {
+        Ok(match (self, follow) {
+            (Input::File(path), Some(notices)) => {
+                let mut reader = FollowReader::open(path)?.with_notices(notices.clone());
+                reader.seek_to(offset)?;
+                Box::new(BufReader::new(reader))
+            }
+            _ => {
+                file.seek(SeekFrom::Start(offset))?;
+                Box::new(BufReader::new(file))
+            }
+        })
+    }
+}
+
+/// Turns the input arguments into inputs, where '-' is standard input and
+/// glob patterns are expanded in alphabetical order.
+pub fn expand(args: &[PathBuf]) -> anyhow::Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+    for arg in args {
+        let pattern = arg.to_str().unwrap_or_default();
+        if pattern == "-" {
+            inputs.push(Input::Stdin);
+        } else if arg.exists() || !pattern.contains(['*', '?', '[']) {
+            inputs.push(Input::File(arg.clone()));
+        } else {
+            let len = inputs.len();
+            for path in glob::glob(pattern)? {
+                inputs.push(Input::File(path?));
+            }
+            if inputs.len() == len {
+                anyhow::bail!("no files match '{pattern}'");
+            }
+        }
+    }
+    Ok(inputs)
+}
+
+/// The compression formats recognized by their magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Picks the compression format from the first bytes of the input.
+    fn detect(start: &[u8]) -> Option<Self> {
+        match start {
+            [0x1F, 0x8B, ..] => Some(Compression::Gzip),
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Some(Compression::Zstd),
+            [b'B', b'Z', b'h', ..] => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+}
+
+/// Wraps a reader in a decompressor if the input is gzip, zstd or bzip2
+/// compressed, and returns it unchanged otherwise.
+pub fn decompressed<'a, R: Read + 'a>(mut reader: R) -> std::io::Result<Box<dyn BufRead + 'a>> {
+    let mut start = Vec::new();
+    reader.by_ref().take(4).read_to_end(&mut start)?;
+    let compression = Compression::detect(&start);
+    let reader = BufReader::new(Cursor::new(start).chain(reader));
+    Ok(match compression {
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Some(Compression::Bzip2) => Box::new(BufReader::new(MultiBzDecoder::new(reader))),
+        None => Box::new(reader),
+    })
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const TEXT: &str = "{\"message\": \"Hi\"}\n";
+
+    fn read_all(input: Vec<u8>) -> String {
+        let mut text = String::new();
+        decompressed(input.as_slice())
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn reads_compressed_input() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(TEXT.as_bytes()).unwrap();
+        assert_eq!(read_all(gzip.finish().unwrap()), TEXT);
+
+        let zstd = zstd::encode_all(TEXT.as_bytes(), 0).unwrap();
+        assert_eq!(read_all(zstd), TEXT);
+
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        bzip2.write_all(TEXT.as_bytes()).unwrap();
+        assert_eq!(read_all(bzip2.finish().unwrap()), TEXT);
+    }
+
+    #[test]
+    fn expands_glob_patterns() {
+        let dir = std::env::temp_dir().join(format!("jl-cat-expand-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.log", "a.log", "c.txt"] {
+            File::create(dir.join(name)).unwrap();
+        }
+        let inputs = expand(&[
+            dir.join("*.log"),
+            "-".into(),
+            dir.join("c.txt"),
+            "missing.log".into(),
+        ])
+        .unwrap();
+        assert_eq!(
+            inputs,
+            [
+                Input::File(dir.join("a.log")),
+                Input::File(dir.join("b.log")),
+                Input::Stdin,
+                Input::File(dir.join("c.txt")),
+                Input::File("missing.log".into()),
+            ]
+        );
+        assert!(expand(&[dir.join("*.gz")]).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn labels_are_short_file_names() {
+        for (path, label) in [
+            ("/var/log/api.log.1.gz", "api"),
+            ("worker", "worker"),
+            (".hidden.log", "hidden"),
+        ] {
+            assert_eq!(Input::File(path.into()).label(), label);
+        }
+        assert_eq!(Input::Stdin.label(), "stdin");
+    }
+
+    #[test]
+    fn reads_uncompressed_input() {
+        assert_eq!(read_all(TEXT.into()), TEXT);
+        assert_eq!(read_all(b"{}".to_vec()), "{}");
+        assert_eq!(read_all(Vec::new()), "");
+    }
+}
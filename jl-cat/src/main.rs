@@ -1,54 +1,176 @@
+mod follow;
+mod input;
+mod rotated;
+mod seek;
+mod tail;
+
 use std::{
-    fs::File,
-    io::{BufReader, IsTerminal},
+    io::{BufRead, IsTerminal, Write},
     path::PathBuf,
+    process::ExitCode,
 };
 
-use jl_proc::{LogEntryFormatter, LogEntryIterator, LogEntryProcessor, ProcessorOptions};
+use follow::Notices;
+use input::Input;
+use jiff::{Zoned, tz::TimeZone};
+use jl_proc::{
+    FieldSchema, Grep, InputFormat, JsonStreamIterator, LevelRange, LineItem, LogEntryFormatter,
+    LogEntryIterator, LogEntryMerger, LogEntryProcessor, NumericLevels, PrefixExtractor, Preset,
+    ProcessorOptions, Query, RecordDelimiter, TimeBound, TimeDisplay, TimeRange,
+};
+use regex::RegexBuilder;
 
 // --------------------------------------------------------------------------
 
-fn main() -> std::result::Result<(), anyhow::Error> {
+fn main() -> std::result::Result<ExitCode, anyhow::Error> {
     let cli = Cli::parse();
+    let inputs = input::expand(&cli.input_files)?;
+    if cli.follow && inputs.len() > 1 {
+        anyhow::bail!("--follow takes a single input");
+    }
+    let files = cli.files(inputs)?;
+    if cli.follow
+        && cli.tail.is_some()
+        && let Some((_, input)) = files.last()
+        && !input.is_seekable()?
+    {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--tail with --follow needs an uncompressed file, not '{}'",
+                    input.name()
+                ),
+            )
+            .exit();
+    }
+    let time_zone = match cli.tz.as_deref() {
+        Some("local") => Some(TimeZone::system()),
+        Some(name) => Some(TimeZone::get(name)?),
+        None => None,
+    };
+    // times of day and times ago are in the time zone shown
+    let now = Zoned::now().with_time_zone(time_zone.clone().unwrap_or_else(TimeZone::system));
+    let time_range = cli.time_range(&now)?;
+    let grep = cli.grep()?;
     let options = ProcessorOptions {
         skip_empty_lines: cli.skip_empty_lines,
         session_start: cli.session_start.clone(),
+        show_sources: cli.shows_sources(&files),
+        flush: cli.follow,
+        time_range,
+        levels: cli.level.clone(),
+        query: cli.query.clone(),
+        grep: grep.clone(),
     };
 
+    let schema = cli.schema()?;
+
     let stdout = std::io::stdout();
     let use_color = stdout.is_terminal() && std::env::var("NO_COLOR").is_err();
-    let mut formatter = LogEntryFormatter::with_options(use_color, cli.no_extras, stdout.lock());
+    let mut formatter = LogEntryFormatter::with_options(use_color, cli.no_extras, stdout.lock())
+        .with_time_display(cli.time, time_zone)
+        .with_text_marker(cli.text_marker.clone().unwrap_or_default());
+    if let Some(grep) = grep.filter(|grep| !grep.invert) {
+        formatter = formatter.with_highlight(grep.regex);
+    }
+    if cli.merge || cli.rotated {
+        let width = files
+            .iter()
+            .map(|(_, file)| cli.label(file).chars().count())
+            .max();
+        formatter = formatter.with_label_width(width.unwrap_or_default());
+    }
 
     let processor = LogEntryProcessor::new(options);
-    if cli.use_std_input() {
-        let reader = std::io::stdin().lock();
-        let entries = LogEntryIterator::from_buf_reader(reader);
-        processor.process_entries(entries, "<STDIN>", &mut formatter)?;
+    let all_opened = cli.process(&files, &processor, time_range, &schema, &mut formatter)?;
+    Ok(if all_opened {
+        ExitCode::SUCCESS
     } else {
-        let input_file = File::open(&cli.input_file)?;
-        let reader = BufReader::new(input_file);
-        let entries = LogEntryIterator::from_buf_reader(reader);
-        processor.process_entries(
-            entries,
-            cli.input_file.to_str().unwrap_or("<n/a>"),
-            &mut formatter,
-        )?;
-    };
+        ExitCode::FAILURE
+    })
+}
+
+/// Reports an error opening an input after the output so far.
+fn report<W: Write>(
+    error: &anyhow::Error,
+    formatter: &mut LogEntryFormatter<W>,
+) -> std::io::Result<()> {
+    formatter.flush()?;
+    eprintln!("Error: {error:#}");
     Ok(())
 }
 
 // --------------------------------------------------------------------------
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, error::ErrorKind};
 
 /// Command-line interface for showing json log entries in a human-friendly
 /// format.
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    /// Input file to process. Use '-' for standard input.
-    #[arg(value_name = "FILE")]
-    input_file: PathBuf,
+    /// Input files to process, in order, or glob patterns like 'logs/*.log'.
+    /// Use '-' for standard input.
+    #[arg(value_name = "FILE", required = true)]
+    input_files: Vec<PathBuf>,
+    /// Interleave the entries of all inputs in timestamp order, with a label
+    /// for the input in front of each entry.
+    #[arg(short, long)]
+    merge: bool,
+    /// Keep reading the input as it grows, like 'tail -F', reopening it
+    /// when it is rotated or truncated.
+    #[arg(short, long, conflicts_with = "merge")]
+    follow: bool,
+    /// Show only the last N lines of each input. Files are read from the
+    /// end, and their line numbers are counted from the first line shown.
+    #[arg(short = 'n', long, value_name = "N", conflicts_with_all = ["merge", "multiline", "rotated"])]
+    tail: Option<usize>,
+    /// Show only entries at or after this time: a timestamp like
+    /// '2024-03-15T12:00' (in UTC without an offset), a time of day today
+    /// like '10:30' (in the --tz time zone or the local one), or a time ago
+    /// like '15m ago' or '-2h'. Sorted files are searched for the start
+    /// instead of being read from the beginning.
+    #[arg(long, value_name = "TIME")]
+    since: Option<TimeBound>,
+    /// Show only entries at or before this time, written like for --since.
+    #[arg(long, value_name = "TIME")]
+    until: Option<TimeBound>,
+    /// Read each input file together with its rotated files, like
+    /// 'app.log.2.gz', 'app.log.1' and 'app.log-20240315' for 'app.log',
+    /// oldest first, as one input with the file name in front of each entry.
+    #[arg(short, long)]
+    rotated: bool,
+    /// Show only entries with this level or a more severe one, like 'warn',
+    /// or with a level in a range, like 'error..fatal'. Entries without a
+    /// known level are shown only with 'verbose'.
+    #[arg(long, value_name = "LEVEL")]
+    level: Option<LevelRange>,
+    /// Show only entries matching a condition on their fields, like
+    /// 'status >= 500 && path =~ "^/api"'. Fields are extras, with nested
+    /// fields like 'req.headers.host', or timestamp, level, message and
+    /// label.
+    #[arg(long = "where", value_name = "EXPR")]
+    query: Option<Query>,
+    /// Show only entries with a match for this regular expression in their
+    /// message or extras values, with the matches highlighted.
+    #[arg(long, value_name = "PATTERN")]
+    grep: Option<String>,
+    /// Ignore case in the --grep pattern.
+    #[arg(short, long, requires = "grep")]
+    ignore_case: bool,
+    /// Show the entries without a match for --grep instead.
+    #[arg(short = 'v', long, requires = "grep")]
+    invert_match: bool,
+    /// Show N entries after each match of --grep.
+    #[arg(short = 'A', long, value_name = "N", requires = "grep")]
+    after_context: Option<usize>,
+    /// Show N entries before each match of --grep.
+    #[arg(short = 'B', long, value_name = "N", requires = "grep")]
+    before_context: Option<usize>,
+    /// Show N entries before and after each match of --grep.
+    #[arg(short = 'C', long, value_name = "N", requires = "grep")]
+    context: Option<usize>,
     /// Skip empty lines in the input.
     #[arg(long)]
     skip_empty_lines: bool,
@@ -58,10 +180,435 @@ struct Cli {
     /// Skip printing extras (additional fields beyond timestamp, level, message).
     #[arg(long)]
     no_extras: bool,
+    /// The format of the input lines. One of: json, logfmt, auto (json for
+    /// lines starting with '{', logfmt otherwise).
+    #[arg(long, value_name = "FORMAT", default_value = "json")]
+    format: InputFormat,
+    /// Read json objects that span several lines, like pretty-printed
+    /// objects one after another or wrapped in an array.
+    #[arg(long, conflicts_with_all = ["format", "prefix", "prefix_regex", "delimiter"])]
+    multiline: bool,
+    /// The byte ending each record. One of: newline, rs (json text
+    /// sequences), nul, or a single character. By default, json text
+    /// sequences are detected when the input starts with 0x1E.
+    #[arg(long, value_name = "DELIM")]
+    delimiter: Option<RecordDelimiter>,
+    /// Show this marker in front of lines that aren't json, e.g. '| '.
+    #[arg(long, value_name = "STRING")]
+    text_marker: Option<String>,
+    /// Read lines with a prefix in front of the json. One of: kubectl (logs
+    /// --prefix), cri, compose, syslog.
+    #[arg(long, value_name = "FORMAT", conflicts_with = "prefix_regex")]
+    prefix: Option<PrefixExtractor>,
+    /// Read lines with a prefix matching this regular expression in front of
+    /// the json. The optional 'label' group is shown in front of each entry.
+    #[arg(long, value_name = "REGEX")]
+    prefix_regex: Option<String>,
+    /// Treat entries missing a timestamp, level or message as parse errors.
+    #[arg(long)]
+    strict: bool,
+    /// Read the log format of a common structured logger. One of: pino,
+    /// bunyan, winston, zap, logrus, slog, structlog, clef, ecs, gcp,
+    /// journald.
+    #[arg(long, value_name = "NAME")]
+    preset: Option<Preset>,
+    /// Show the fields a preset hides, like the '_' and '__' fields added by
+    /// journald.
+    #[arg(long)]
+    show_hidden: bool,
+    /// Set the keys to look for a core field in, e.g. 'level=severity,lvl'.
+    /// Core fields are 'timestamp', 'level' and 'message'. Can be repeated.
+    #[arg(long, value_name = "FIELD=KEYS")]
+    field_map: Vec<String>,
+    /// How to read numeric levels. One of: auto (syslog for 0-7, bunyan
+    /// otherwise), syslog, bunyan, otel.
+    #[arg(long, value_name = "SCALE")]
+    numeric_levels: Option<NumericLevels>,
+    /// Map a level value onto a severity, e.g. '35=warn'. Severities are
+    /// fatal, error, warn, info, debug and verbose. Can be repeated.
+    #[arg(long, value_name = "VALUE=LEVEL")]
+    level_map: Vec<String>,
+    /// How to show timestamps. One of: time (the time of day), full (date,
+    /// time and offset), relative (time since the first entry), delta (time
+    /// since the previous entry).
+    #[arg(long, value_name = "MODE", default_value = "time")]
+    time: TimeDisplay,
+    /// Show timestamps in this time zone instead of the one they were written
+    /// in. Use 'local' for the local time zone, or an IANA name like
+    /// 'Europe/Copenhagen'.
+    #[arg(long, value_name = "ZONE")]
+    tz: Option<String>,
 }
 
 impl Cli {
-    fn use_std_input(&self) -> bool {
-        self.input_file.to_str() == Some("-")
+    /// Returns the files to read, each with the input it was found for, as
+    /// named in headers. The files of a rotated set are read as one input.
+    fn files(&self, inputs: Vec<Input>) -> anyhow::Result<Vec<(Input, Input)>> {
+        if self.rotated {
+            return rotated::expand(inputs);
+        }
+        Ok(inputs
+            .into_iter()
+            .map(|input| (input.clone(), input))
+            .collect())
+    }
+
+    /// Returns true if a header is shown whenever the input changes, as
+    /// there is more than one and they aren't merged.
+    fn shows_sources(&self, files: &[(Input, Input)]) -> bool {
+        !self.merge && files.windows(2).any(|pair| pair[0].0 != pair[1].0)
+    }
+
+    /// Returns the label shown in front of the entries of a file, which is
+    /// its file name in a rotated set.
+    fn label<'a>(&self, file: &'a Input) -> &'a str {
+        if self.rotated {
+            file.file_name()
+        } else {
+            file.label()
+        }
+    }
+
+    /// Reads the files and shows their entries. Like cat, files that can't
+    /// be opened are reported and skipped, and false is returned if there
+    /// were any.
+    fn process<W: Write>(
+        &self,
+        files: &[(Input, Input)],
+        processor: &LogEntryProcessor,
+        range: TimeRange,
+        schema: &FieldSchema,
+        formatter: &mut LogEntryFormatter<W>,
+    ) -> std::io::Result<bool> {
+        let mut all_opened = true;
+        if self.merge {
+            let mut opened = Vec::new();
+            for (_, file) in files {
+                match file
+                    .open()
+                    .and_then(|reader| self.entries(reader, schema.clone()))
+                {
+                    Ok(entries) => opened.push((file, entries)),
+                    Err(e) => {
+                        report(&e, formatter)?;
+                        all_opened = false;
+                    }
+                }
+            }
+            let labels = opened
+                .iter()
+                .map(|(file, _)| self.label(file).into())
+                .collect();
+            let names: Vec<_> = opened.iter().map(|(file, _)| file.name()).collect();
+            let streams = opened.into_iter().map(|(_, entries)| entries).collect();
+            let merged = LogEntryMerger::new(streams).with_labels(labels);
+            processor.process_merged(merged, &names, formatter)?;
+            return Ok(all_opened);
+        }
+        // the rotated files of a log are read before following the log itself
+        for (i, (input, file)) in files.iter().enumerate() {
+            let follow = self.follow && i + 1 == files.len();
+            let entries = match self.read(file, follow, range, schema.clone(), formatter) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    report(&e, formatter)?;
+                    all_opened = false;
+                    continue;
+                }
+            };
+            if !self.rotated {
+                processor.process_entries(entries, input.name(), formatter)?;
+                continue;
+            }
+            let label = self.label(file).to_string();
+            let entries = entries.map(move |mut item| {
+                if let LineItem::Entry(entry) = &mut item {
+                    entry.add_label(&label);
+                }
+                item
+            });
+            processor.process_entries(entries, input.name(), formatter)?;
+        }
+        Ok(all_opened)
+    }
+
+    /// Opens an input for reading, from the start, only its last lines or
+    /// from the start of the time range, following it if asked to. Line
+    /// numbers of inputs not read from the start are counted from the first
+    /// line read, and marked as such. A followed file being rotated or
+    /// truncated is shown as a notice.
+    fn read<W: Write>(
+        &self,
+        input: &Input,
+        follow: bool,
+        range: TimeRange,
+        schema: FieldSchema,
+        formatter: &mut LogEntryFormatter<W>,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = LineItem>>> {
+        if !follow {
+            return self.open(input, None, range, schema, formatter);
+        }
+        let notices = Notices::default();
+        let entries = self.open(input, Some(&notices), range, schema, formatter)?;
+        Ok(Box::new(notices.interleave(entries)))
+    }
+
+    /// Opens an input for `read`, following it if given notices to report
+    /// to.
+    fn open<W: Write>(
+        &self,
+        input: &Input,
+        follow: Option<&Notices>,
+        range: TimeRange,
+        schema: FieldSchema,
+        formatter: &mut LogEntryFormatter<W>,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = LineItem>>> {
+        formatter.set_line_prefix("");
+        if let Some(lines) = self.tail {
+            if let Some(reader) = input.open_tail(lines, follow)? {
+                formatter.set_line_prefix("tail+");
+                return self.entries(reader, schema);
+            }
+            let entries = self.entries(input.open()?, schema)?;
+            return Ok(Box::new(tail::last_lines(entries, lines).into_iter()));
+        }
+        // only the start of the range lets the search skip part of the file
+        if let Some(since) = range.since
+            && self.is_line_based()
+        {
+            let prefix = self.prefix()?;
+            let timestamp_of = |line: &str| {
+                let mut entries = LogEntryIterator::with_schema(line.as_bytes(), schema.clone())
+                    .with_format(self.format);
+                if let Some(prefix) = &prefix {
+                    entries = entries.with_prefix(prefix.clone());
+                }
+                entries.find_map(|item| instant(&item))
+            };
+            let is_before = |instant| instant < since;
+            if let Some((reader, offset)) = input.open_search(is_before, timestamp_of, follow)? {
+                if offset > 0 {
+                    formatter.set_line_prefix("since+");
+                }
+                // entries after the end of the range are left to the processor,
+                // as a file that is mostly sorted can have more entries in the
+                // range after them
+                return self.entries(reader, schema);
+            }
+        }
+        let reader = match follow {
+            Some(notices) => input.follow(notices)?,
+            None => input.open()?,
+        };
+        self.entries(reader, schema)
+    }
+
+    /// Returns the schema of the preset, with the options that change how
+    /// entries are read applied to it. Json messages, like those in the
+    /// journal, are read with the same options.
+    fn schema(&self) -> anyhow::Result<FieldSchema> {
+        let mut schema = self.preset.map(|p| p.schema()).unwrap_or_default();
+        self.apply_schema_options(&mut schema)?;
+        if let Some(message_schema) = &mut schema.message_schema {
+            self.apply_schema_options(message_schema)?;
+        }
+        Ok(schema)
+    }
+
+    /// Applies the options that change how entries are read to the schema.
+    fn apply_schema_options(&self, schema: &mut FieldSchema) -> anyhow::Result<()> {
+        for mapping in &self.field_map {
+            schema.apply_mapping(mapping).map_err(anyhow::Error::msg)?;
+        }
+        schema.strict = self.strict;
+        if self.show_hidden {
+            schema.hidden_prefixes.clear();
+        }
+        if let Some(numeric_levels) = self.numeric_levels {
+            schema.numeric_levels = numeric_levels;
+        }
+        for mapping in &self.level_map {
+            schema
+                .apply_level_mapping(mapping)
+                .map_err(anyhow::Error::msg)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the search for --grep, if any.
+    fn grep(&self) -> anyhow::Result<Option<Grep>> {
+        let Some(pattern) = &self.grep else {
+            return Ok(None);
+        };
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(self.ignore_case)
+            .build()?;
+        Ok(Some(Grep {
+            invert: self.invert_match,
+            before: self.before_context.or(self.context).unwrap_or_default(),
+            after: self.after_context.or(self.context).unwrap_or_default(),
+            ..Grep::new(regex)
+        }))
+    }
+
+    /// Returns the time range of the entries to show, with times relative
+    /// to `now`.
+    fn time_range(&self, now: &Zoned) -> anyhow::Result<TimeRange> {
+        Ok(TimeRange {
+            since: self.since.map(|bound| bound.resolve(now)).transpose()?,
+            until: self.until.map(|bound| bound.resolve(now)).transpose()?,
+        })
+    }
+
+    /// Returns true if each line of the input is read on its own, so the
+    /// input can be searched line by line.
+    fn is_line_based(&self) -> bool {
+        !self.multiline && self.delimiter.is_none_or(|d| d == RecordDelimiter::Newline)
+    }
+
+    /// Returns the prefix extractor for the input lines, if any.
+    fn prefix(&self) -> anyhow::Result<Option<PrefixExtractor>> {
+        Ok(match (&self.prefix, &self.prefix_regex) {
+            (Some(prefix), _) => Some(prefix.clone()),
+            (None, Some(pattern)) => Some(PrefixExtractor::regex(pattern)?),
+            (None, None) => None,
+        })
+    }
+
+    /// Creates the iterator over the entries of the input, with the input
+    /// format and prefix extractor given.
+    fn entries<'a, B: BufRead + 'a>(
+        &self,
+        reader: B,
+        schema: FieldSchema,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = LineItem> + 'a>> {
+        if self.multiline {
+            return Ok(Box::new(JsonStreamIterator::with_schema(reader, schema)));
+        }
+        let mut entries = LogEntryIterator::with_schema(reader, schema).with_format(self.format);
+        if let Some(delimiter) = self.delimiter {
+            entries = entries.with_delimiter(delimiter);
+        }
+        Ok(Box::new(match self.prefix()? {
+            Some(prefix) => entries.with_prefix(prefix),
+            None => entries,
+        }))
+    }
+}
+
+/// Returns the instant of an entry, if it has a timestamp that can be parsed.
+fn instant(item: &LineItem) -> Option<jiff::Timestamp> {
+    match item {
+        LineItem::Entry(entry) => entry.timestamp.as_ref().and_then(|t| t.instant()),
+        _ => None,
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_json_messages_with_the_schema_options() {
+        let cli = Cli::parse_from([
+            "jl-cat",
+            "--preset=journald",
+            "--level-map=35=warn",
+            "--field-map=message=MESSAGE,text",
+            "-",
+        ]);
+        let schema = cli.schema().unwrap();
+        let json = r#"{"PRIORITY":"6","MESSAGE":"{\"level\":35,\"text\":\"Slow\"}"}"#;
+        let Some(LineItem::Entry(entry)) =
+            LogEntryIterator::with_schema(json.as_bytes(), schema).next()
+        else {
+            panic!("not an entry");
+        };
+        assert_eq!(entry.level(), jl_proc::SeverityLevel::Warn);
+        assert_eq!(entry.message, "Slow");
+    }
+
+    #[test]
+    fn shows_entries_after_one_out_of_order() {
+        // a sorted file with one entry written late, after the search point
+        let path = std::env::temp_dir().join(format!("jl-cat-until-{}.log", std::process::id()));
+        let text: String = (0..2000)
+            .map(|m| if m == 1500 { 1900 } else { m })
+            .map(|m| {
+                let time = jiff::Timestamp::from_second(m * 60).unwrap();
+                format!(
+                    "{{\"timestamp\": \"{time}\", \"level\": \"info\", \"message\": \"m{m}\"}}\n"
+                )
+            })
+            .collect();
+        std::fs::write(&path, text).unwrap();
+        let cli = Cli::parse_from([
+            "jl-cat",
+            "--since=1970-01-01T16:40:00Z",
+            "--until=1970-01-02T02:40:00Z",
+            path.to_str().unwrap(),
+        ]);
+        let range = cli.time_range(&Zoned::now()).unwrap();
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output);
+        let input = Input::File(path.clone());
+        let entries = cli
+            .read(&input, false, range, FieldSchema::default(), &mut formatter)
+            .unwrap();
+        let processor = LogEntryProcessor::new(ProcessorOptions {
+            time_range: range,
+            ..Default::default()
+        });
+        processor
+            .process_entries(entries, input.name(), &mut formatter)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let messages: Vec<_> = output.lines().map(|line| &line[19..]).collect();
+        // minutes 1000 to 1600, without the late entry
+        assert_eq!(messages.len(), 600);
+        assert_eq!(messages.first(), Some(&"m1000"));
+        assert_eq!(messages.last(), Some(&"m1600"));
+        assert!(!messages.contains(&"m1900"));
+    }
+
+    #[test]
+    fn reads_rotated_files_as_one_input() {
+        let dir = std::env::temp_dir().join(format!("jl-cat-main-rotated-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, message) in [("app.log.1", "old"), ("app.log", "new")] {
+            let line = format!(
+                "{{\"timestamp\": \"2024-01-01T10:00:00Z\", \"level\": \"info\", \"message\": \"{message}\"}}\n"
+            );
+            std::fs::write(dir.join(name), line).unwrap();
+        }
+        let path = dir.join("app.log");
+        let cli = Cli::parse_from(["jl-cat", "--rotated", path.to_str().unwrap()]);
+        let files = cli.files(input::expand(&cli.input_files).unwrap()).unwrap();
+        let processor = LogEntryProcessor::new(ProcessorOptions {
+            show_sources: cli.shows_sources(&files),
+            ..Default::default()
+        });
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output);
+        let all_opened = cli
+            .process(
+                &files,
+                &processor,
+                TimeRange::default(),
+                &FieldSchema::default(),
+                &mut formatter,
+            )
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(all_opened);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "app.log.1 10:00:00.000 [inf] old\n\
+app.log   10:00:00.000 [inf] new\n"
+        );
     }
 }
@@ -0,0 +1,192 @@
+use std::{
+    cmp::Reverse,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::input::Input;
+
+// --------------------------------------------------------------------------
+
+/// The extensions of compressed rotated files, left off before reading the
+/// rotation suffix.
+const COMPRESSED_EXTENSIONS: [&str; 3] = [".gz", ".zst", ".bz2"];
+
+/// Replaces each file input with the files of its rotated set, oldest first,
+/// each paired with the input it was found for. Standard input is kept as it
+/// is.
+pub fn expand(inputs: Vec<Input>) -> anyhow::Result<Vec<(Input, Input)>> {
+    let mut expanded = Vec::new();
+    for input in inputs {
+        let files = match &input {
+            Input::Stdin => vec![Input::Stdin],
+            Input::File(base) => discover(base)?.into_iter().map(Input::File).collect(),
+        };
+        expanded.extend(files.into_iter().map(|file| (input.clone(), file)));
+    }
+    Ok(expanded)
+}
+
+/// Finds the rotated files of a log in the same directory, oldest first, and
+/// the log itself last.
+///
+/// Rotated files have a number or a date after the name of the log, like
+/// `app.log.1`, `app.log.2.gz` or `app.log-20240315`, or a date in front of
+/// its extension, like `app-2024-03-15.log`. Higher numbers are older, and
+/// dated files are older than numbered ones.
+pub fn discover(base: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let name = base
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("'{}' isn't a file name", base.display()))?;
+    let dir = match base.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("can't read '{}'", dir.display()))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if let Some(rotation) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| Rotation::of(name, n))
+        {
+            files.push((rotation, base.with_file_name(entry.file_name())));
+        }
+    }
+    if files.is_empty() {
+        anyhow::bail!("no files found for '{}'", base.display());
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Where a file stands in a rotated set, ordered from oldest to newest.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Rotation {
+    /// Rotated with a date, as its digits
+    Dated(String),
+    /// Rotated with a number, where higher numbers are older
+    Numbered(Reverse<u64>),
+    /// The log that is still being written
+    Current,
+}
+
+impl Rotation {
+    /// Reads the rotation of a file from its name, or returns `None` if it
+    /// isn't part of the set of the base name.
+    fn of(base: &str, name: &str) -> Option<Self> {
+        if name == base {
+            return Some(Rotation::Current);
+        }
+        let name = COMPRESSED_EXTENSIONS
+            .iter()
+            .find_map(|ext| name.strip_suffix(ext))
+            .unwrap_or(name);
+        if let Some(suffix) = name.strip_prefix(base) {
+            return Self::from_suffix(suffix);
+        }
+        // a date between the stem and the extension, like app-2024-03-15.log
+        let (stem, ext) = base.rsplit_once('.').filter(|(stem, _)| !stem.is_empty())?;
+        let middle = name
+            .strip_prefix(stem)?
+            .strip_suffix(ext)?
+            .strip_suffix('.')?;
+        Self::from_suffix(middle)
+    }
+
+    /// Reads a suffix like `.1` or `-20240315`.
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        let suffix = suffix.strip_prefix(['.', '-', '_'])?;
+        if suffix.is_empty() || !suffix.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        if suffix.len() < 8 {
+            return suffix.parse().ok().map(|n| Rotation::Numbered(Reverse(n)));
+        }
+        // dates and times, with any separators between their parts
+        if suffix
+            .chars()
+            .all(|c| c.is_ascii_digit() || "-_.T:".contains(c))
+        {
+            let digits = suffix.chars().filter(char::is_ascii_digit).collect();
+            return Some(Rotation::Dated(digits));
+        }
+        None
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn reads_rotation_suffixes() {
+        for (name, rotation) in [
+            ("app.log", Some(Rotation::Current)),
+            ("app.log.1", Some(Rotation::Numbered(Reverse(1)))),
+            ("app.log.12.gz", Some(Rotation::Numbered(Reverse(12)))),
+            ("app.log-20240315", Some(Rotation::Dated("20240315".into()))),
+            (
+                "app.log.2024-03-15.zst",
+                Some(Rotation::Dated("20240315".into())),
+            ),
+            (
+                "app-2024-03-15.log",
+                Some(Rotation::Dated("20240315".into())),
+            ),
+            (
+                "app.2024-03-15_10.log.gz",
+                Some(Rotation::Dated("2024031510".into())),
+            ),
+            ("app.log.gz", None),
+            ("app.log.bak", None),
+            ("app.logs", None),
+            ("api.log.1", None),
+        ] {
+            assert_eq!(Rotation::of("app.log", name), rotation, "{name}");
+        }
+    }
+
+    #[test]
+    fn finds_rotated_files_oldest_first() {
+        let dir = std::env::temp_dir().join(format!("jl-cat-rotated-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "app.log",
+            "app.log.1",
+            "app.log.2.gz",
+            "app.log.10.gz",
+            "app.log-20240301.gz",
+            "app.log-20240215",
+            "other.log.1",
+        ] {
+            File::create(dir.join(name)).unwrap();
+        }
+        let files = discover(&dir.join("app.log")).unwrap();
+        let names: Vec<_> = files
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "app.log-20240215",
+                "app.log-20240301.gz",
+                "app.log.10.gz",
+                "app.log.2.gz",
+                "app.log.1",
+                "app.log",
+            ]
+        );
+        assert!(discover(&dir.join("missing.log")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+};
+
+// --------------------------------------------------------------------------
+
+/// How many lines to look at for a timestamp after each probe offset.
+const PROBE_LINES: usize = 100;
+
+/// Below this many bytes, the rest is left to the linear scan.
+const MIN_SPAN: u64 = 4096;
+
+/// Binary searches the byte offsets of a file sorted by timestamp, for an
+/// offset to start reading from that skips (most of) the entries for which
+/// `is_before` is true.
+///
+/// The offset returned is the start of a line, and all entries before it are
+/// `is_before`. Entries between it and the first entry that isn't are left
+/// to be filtered out while reading. `timestamp_of` reads the timestamp of a
+/// line, if it has one.
+///
+/// Returns `None` if the timestamps found while searching are out of order,
+/// so the file has to be scanned from the start. Only the lines looked at
+/// are checked, so entries after the offset are still filtered one by one.
+pub fn search(
+    input: &mut (impl Read + Seek),
+    is_before: impl Fn(jiff::Timestamp) -> bool,
+    timestamp_of: impl Fn(&str) -> Option<jiff::Timestamp>,
+) -> std::io::Result<Option<u64>> {
+    let mut lo = 0;
+    let mut hi = input.seek(SeekFrom::End(0))?;
+    let mut probes = BTreeMap::new();
+    while hi - lo > MIN_SPAN {
+        let mid = lo + (hi - lo) / 2;
+        let probe = probe(input, mid, &timestamp_of)?;
+        match probe {
+            Some(Probe {
+                start,
+                end,
+                timestamp,
+            }) if start < hi => {
+                // each timestamp has to be between those found before and
+                // after it in the file
+                let before = probes.range(..start).next_back().map(|(_, t)| *t);
+                let after = probes.range(start..).next().map(|(_, t)| *t);
+                if before.is_some_and(|t| t > timestamp) || after.is_some_and(|t| t < timestamp) {
+                    return Ok(None);
+                }
+                probes.insert(start, timestamp);
+                if is_before(timestamp) {
+                    lo = end;
+                } else {
+                    hi = mid;
+                }
+            }
+            _ => hi = mid,
+        }
+    }
+    Ok(Some(lo))
+}
+
+/// A line with a timestamp, found after a probe offset.
+struct Probe {
+    start: u64,
+    end: u64,
+    timestamp: jiff::Timestamp,
+}
+
+/// Finds the first line with a timestamp that starts after the offset.
+fn probe(
+    input: &mut (impl Read + Seek),
+    offset: u64,
+    timestamp_of: impl Fn(&str) -> Option<jiff::Timestamp>,
+) -> std::io::Result<Option<Probe>> {
+    input.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(input);
+    let mut line = Vec::new();
+    // the line at the offset is only partly read
+    let mut start = offset + reader.read_until(b'\n', &mut line)? as u64;
+    for _ in 0..PROBE_LINES {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)? as u64;
+        if n == 0 {
+            break;
+        }
+        if let Some(timestamp) = timestamp_of(&String::from_utf8_lossy(&line)) {
+            return Ok(Some(Probe {
+                start,
+                end: start + n,
+                timestamp,
+            }));
+        }
+        start += n;
+    }
+    Ok(None)
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn minute(line: &str) -> Option<jiff::Timestamp> {
+        let minute = line
+            .strip_prefix("m")?
+            .split(' ')
+            .next()?
+            .parse::<i64>()
+            .ok()?;
+        jiff::Timestamp::from_second(minute * 60).ok()
+    }
+
+    fn log(minutes: impl Iterator<Item = i64>) -> String {
+        minutes
+            .map(|m| format!("m{m} {}\n", "x".repeat(50)))
+            .collect()
+    }
+
+    fn search_since(text: &str, since: i64) -> Option<u64> {
+        let since = jiff::Timestamp::from_second(since * 60).unwrap();
+        search(&mut Cursor::new(text), |t| t < since, minute).unwrap()
+    }
+
+    #[test]
+    fn finds_an_offset_close_before_the_start() {
+        let text = log(0..10_000);
+        for since in [0, 1, 500, 5000, 9999, 20_000] {
+            let offset = search_since(&text, since).unwrap() as usize;
+            let start = text.find(&format!("m{since} ")).unwrap_or(text.len());
+            // all lines before the offset are skipped correctly, and only a
+            // few lines before the start are left for the linear scan
+            assert!(offset == 0 || text[..offset].ends_with('\n'));
+            assert!(offset <= start, "{since}");
+            assert!(start - offset < 2 * MIN_SPAN as usize, "{since}");
+        }
+    }
+
+    #[test]
+    fn skips_text_lines_between_entries() {
+        let text: String = (0..5000)
+            .map(|m| format!("m{m} entry\n  at trace line\n  at trace line\n"))
+            .collect();
+        let offset = search_since(&text, 4000).unwrap() as usize;
+        assert!(offset > 0);
+        let first = text[offset..].lines().find_map(minute).unwrap();
+        assert!(first.as_second() / 60 <= 4000);
+    }
+
+    #[test]
+    fn notices_out_of_order_timestamps() {
+        let text = log((0..10_000).rev());
+        assert_eq!(search_since(&text, 2500), None);
+    }
+}
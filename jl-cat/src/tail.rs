@@ -0,0 +1,125 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Seek, SeekFrom},
+};
+
+use jl_proc::LineItem;
+
+// --------------------------------------------------------------------------
+
+/// How many bytes to read at a time when searching backwards.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Finds the offset where the last `lines` lines of the input start, by
+/// reading backwards from the end. A newline at the very end doesn't start
+/// another line.
+pub fn tail_offset(input: &mut (impl Read + Seek), lines: usize) -> std::io::Result<u64> {
+    let len = input.seek(SeekFrom::End(0))?;
+    if lines == 0 {
+        return Ok(len);
+    }
+    let mut buf = vec![0; CHUNK_SIZE as usize];
+    let mut end = len;
+    let mut newlines = 0;
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK_SIZE);
+        let chunk = &mut buf[..(end - start) as usize];
+        input.seek(SeekFrom::Start(start))?;
+        input.read_exact(chunk)?;
+        for (i, &byte) in chunk.iter().enumerate().rev() {
+            let offset = start + i as u64;
+            if byte == b'\n' && offset != len - 1 {
+                newlines += 1;
+                if newlines == lines {
+                    return Ok(offset + 1);
+                }
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+/// Keeps the items of the last `n` lines, for inputs that can't be read from
+/// the end. An encoding warning is part of the line after it.
+pub fn last_lines(items: impl Iterator<Item = LineItem>, n: usize) -> VecDeque<LineItem> {
+    let mut last = VecDeque::new();
+    let mut lines = 0;
+    for item in items {
+        let ends_line = !matches!(item, LineItem::EncodingWarning(_));
+        last.push_back(item);
+        if ends_line {
+            lines += 1;
+        }
+        while lines > n {
+            if !matches!(last.pop_front(), Some(LineItem::EncodingWarning(_)) | None) {
+                lines -= 1;
+            }
+        }
+    }
+    last
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn tail(text: &str, lines: usize) -> &str {
+        let offset = tail_offset(&mut Cursor::new(text), lines).unwrap();
+        &text[offset as usize..]
+    }
+
+    #[test]
+    fn finds_the_last_lines() {
+        assert_eq!(tail("a\nb\nc\n", 2), "b\nc\n");
+        assert_eq!(tail("a\nb\nc", 2), "b\nc");
+        assert_eq!(tail("a\nb\nc\n", 5), "a\nb\nc\n");
+        assert_eq!(tail("a\n\n\nb\n", 2), "\nb\n");
+        assert_eq!(tail("a\nb\n", 0), "");
+        assert_eq!(tail("", 3), "");
+    }
+
+    #[test]
+    fn reads_across_chunks() {
+        let line = format!("{}\n", "x".repeat(1000));
+        let text = line.repeat(200);
+        assert_eq!(tail(&text, 150), line.repeat(150));
+    }
+
+    #[test]
+    fn keeps_the_last_lines() {
+        let line_numbers = |items: Vec<LineItem>, n| -> Vec<_> {
+            last_lines(items.into_iter(), n)
+                .iter()
+                .map(|item| match item {
+                    LineItem::EncodingWarning(line_no) => format!("warning {line_no}"),
+                    LineItem::Text(line_no, _) | LineItem::EmptyLine(line_no) => {
+                        line_no.to_string()
+                    }
+                    _ => "other".into(),
+                })
+                .collect()
+        };
+        let items = || {
+            vec![
+                LineItem::Text(1, "a".into()),
+                LineItem::EncodingWarning(2),
+                LineItem::Text(2, "b\u{FFFD}".into()),
+                LineItem::EmptyLine(3),
+                LineItem::EncodingWarning(4),
+                LineItem::Text(4, "c\u{FFFD}".into()),
+            ]
+        };
+        assert_eq!(line_numbers(items(), 2), ["3", "warning 4", "4"]);
+        assert_eq!(
+            line_numbers(items(), 3),
+            ["warning 2", "2", "3", "warning 4", "4"]
+        );
+        assert_eq!(line_numbers(items(), 9).len(), 6);
+        assert!(line_numbers(items(), 0).is_empty());
+    }
+}
@@ -1,6 +1,8 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
 
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, de::Error as _};
+
+use crate::{FieldSchema, Timestamp};
 
 // --------------------------------------------------------------------------
 
@@ -10,7 +12,7 @@ use serde::Deserialize;
 /// 'timing', which aren't really levels but categories.
 ///
 /// See https://docs.npmjs.com/cli/v8/using-npm/logging
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SeverityLevel {
     Fatal,
     Error,
@@ -34,10 +36,105 @@ impl SeverityLevel {
             SeverityLevel::Other(_) => 6, // Other is the lowest priority
         }
     }
+
+    /// Returns the severity level for a level name, ignoring case.
+    ///
+    /// Besides the npm names, the syslog names (`emerg`, `alert`, `crit`,
+    /// `err`, `notice`, ...) and a few other common spellings are accepted.
+    pub fn from_name(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "fatal" | "panic" | "critical" | "crit" | "alert" | "emerg" | "emergency" => {
+                SeverityLevel::Fatal
+            }
+            "error" | "err" => SeverityLevel::Error,
+            "warn" | "warning" => SeverityLevel::Warn,
+            "info" | "information" | "notice" => SeverityLevel::Info,
+            "debug" => SeverityLevel::Debug,
+            "verbose" | "trace" | "silly" => SeverityLevel::Verbose,
+            _ => SeverityLevel::Other(name.to_string()),
+        }
+    }
+
+    /// Returns the severity level for a numeric level on the given scale.
+    pub fn from_number(n: i64, numeric_levels: NumericLevels) -> Self {
+        match numeric_levels {
+            NumericLevels::Auto if (0..=7).contains(&n) => {
+                Self::from_number(n, NumericLevels::Syslog)
+            }
+            NumericLevels::Auto => Self::from_number(n, NumericLevels::Bunyan),
+            NumericLevels::Syslog => match n {
+                0..=2 => SeverityLevel::Fatal,
+                3 => SeverityLevel::Error,
+                4 => SeverityLevel::Warn,
+                5 | 6 => SeverityLevel::Info,
+                7 => SeverityLevel::Debug,
+                _ => SeverityLevel::Other(n.to_string()),
+            },
+            NumericLevels::Bunyan => match n {
+                ..=10 => SeverityLevel::Verbose,
+                11..=20 => SeverityLevel::Debug,
+                21..=30 => SeverityLevel::Info,
+                31..=40 => SeverityLevel::Warn,
+                41..=50 => SeverityLevel::Error,
+                _ => SeverityLevel::Fatal,
+            },
+            NumericLevels::OpenTelemetry => match n {
+                1..=4 => SeverityLevel::Verbose,
+                5..=8 => SeverityLevel::Debug,
+                9..=12 => SeverityLevel::Info,
+                13..=16 => SeverityLevel::Warn,
+                17..=20 => SeverityLevel::Error,
+                21..=24 => SeverityLevel::Fatal,
+                _ => SeverityLevel::Other(n.to_string()),
+            },
+        }
+    }
+
+    /// Returns the severity level for a level that is either a number on the
+    /// given scale or a name.
+    pub fn parse(level: &str, numeric_levels: NumericLevels) -> Self {
+        match level.trim().parse::<i64>() {
+            Ok(n) => Self::from_number(n, numeric_levels),
+            Err(_) => Self::from_name(level),
+        }
+    }
+}
+
+/// The scale used to map numeric levels onto severity levels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NumericLevels {
+    /// Syslog priorities for 0-7, bunyan levels for anything else
+    #[default]
+    Auto,
+    /// Syslog priorities, 0 (emerg) to 7 (debug)
+    Syslog,
+    /// Bunyan and pino levels, 10 (trace) to 60 (fatal)
+    Bunyan,
+    /// OpenTelemetry severity numbers, 1 (trace) to 24 (fatal4)
+    OpenTelemetry,
+}
+
+impl FromStr for NumericLevels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(NumericLevels::Auto),
+            "syslog" => Ok(NumericLevels::Syslog),
+            "bunyan" | "pino" => Ok(NumericLevels::Bunyan),
+            "otel" | "opentelemetry" => Ok(NumericLevels::OpenTelemetry),
+            other => Err(format!(
+                "unknown numeric levels '{other}', expected one of: auto, syslog, bunyan, otel"
+            )),
+        }
+    }
 }
 
 /// A single log entry from a file/stream of json line-delimited log entries.
 ///
+/// Deserializing a `LogEntry` directly uses the default [`FieldSchema`], use
+/// [`FieldSchema::entry_from_map`] to pick the core fields from other keys.
+///
 /// ### Examples
 /// ```
 /// use jl_proc::*;
@@ -51,24 +148,58 @@ impl SeverityLevel {
 /// }"#;
 ///
 /// let log_entry: LogEntry = serde_json::from_str(json).unwrap();
-/// assert_eq!(log_entry.timestamp, "2024-03-15T12:34:56.123Z");
+/// assert_eq!(log_entry.timestamp.as_ref().unwrap().as_str(), "2024-03-15T12:34:56.123Z");
 /// assert_eq!(log_entry.timestamp_short(), "12:34:56.123");
 /// assert_eq!(log_entry.level(), SeverityLevel::Info);
 /// assert_eq!(log_entry.message, "This is a log message");
 /// assert_eq!(log_entry.extras.len(), 2);
 /// ```
-#[derive(Deserialize, Debug)]
-#[allow(unused)]
+#[derive(Debug)]
 pub struct LogEntry {
-    pub timestamp: String,
-    pub level: String,
+    /// The timestamp, if the entry has one
+    pub timestamp: Option<Timestamp>,
+    /// The level as it appears in the log entry, if it has one
+    pub level: Option<String>,
+    /// The severity the level maps to
+    pub severity: SeverityLevel,
     pub message: String,
-    #[serde(flatten)]
     pub extras: HashMap<String, serde_json::Value>,
+    /// A label for where the entry came from, like a container name
+    pub label: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for LogEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = serde_json::Map::deserialize(deserializer)?;
+        FieldSchema::default()
+            .entry_from_map(map)
+            .map_err(D::Error::custom)
+    }
 }
 
 impl LogEntry {
-    /// Returns the timestamp as a slice in 'shortened' ISO 8601 format.
+    /// Creates a new log entry without extras, using the default level
+    /// mapping.
+    pub fn new(
+        timestamp: impl Into<String>,
+        level: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        let level = level.into();
+        Self {
+            timestamp: Some(Timestamp::parse(timestamp)),
+            severity: SeverityLevel::parse(&level, NumericLevels::Auto),
+            level: Some(level),
+            message: message.into(),
+            extras: HashMap::new(),
+            label: None,
+        }
+    }
+
+    /// Returns the time of day of the timestamp as `HH:MM:SS.mmm`, or an
+    /// empty string if the entry has no timestamp.
+    ///
+    /// Timestamps that can't be parsed are returned as written.
     ///
     /// ### Examples
     /// ```
@@ -82,20 +213,25 @@ impl LogEntry {
     /// assert_eq!(log_entry.timestamp_short(), "12:34:56.123");
     /// assert_eq!(log_entry.level(), SeverityLevel::Info);
     /// ```
-    pub fn timestamp_short(&self) -> &str {
-        &self.timestamp[11..23]
+    pub fn timestamp_short(&self) -> String {
+        self.timestamp
+            .as_ref()
+            .map(Timestamp::time_short)
+            .unwrap_or_default()
     }
 
+    /// Returns the severity level of the entry.
     pub fn level(&self) -> SeverityLevel {
-        match self.level.as_str() {
-            "fatal" => SeverityLevel::Fatal,
-            "error" => SeverityLevel::Error,
-            "warn" | "warning" => SeverityLevel::Warn,
-            "info" => SeverityLevel::Info,
-            "debug" => SeverityLevel::Debug,
-            "verbose" | "trace" | "silly" => SeverityLevel::Verbose,
-            other => SeverityLevel::Other(other.to_string()),
-        }
+        self.severity.clone()
+    }
+
+    /// Puts a label in front of the label of the entry, like `api/web-1`
+    /// for an entry from container `web-1` in the `api` input.
+    pub fn add_label(&mut self, label: &str) {
+        self.label = Some(match &self.label {
+            Some(own) => format!("{label}/{own}"),
+            None => label.to_string(),
+        });
     }
 }
 
@@ -116,7 +252,10 @@ mod tests {
         }"#;
 
         let log_entry: LogEntry = serde_json::from_str(json).unwrap();
-        assert_eq!(log_entry.timestamp, "2024-03-15T12:34:56.042Z");
+        assert_eq!(
+            log_entry.timestamp.as_ref().map(Timestamp::as_str),
+            Some("2024-03-15T12:34:56.042Z")
+        );
         assert_eq!(log_entry.timestamp_short(), "12:34:56.042");
         assert_eq!(log_entry.level(), SeverityLevel::Info);
         assert_eq!(log_entry.message, "This is a log message");
@@ -130,4 +269,47 @@ mod tests {
             &serde_json::Value::from("abc123")
         );
     }
+
+    #[test]
+    fn level_names_ignore_case() {
+        use SeverityLevel as S;
+        for (name, level) in [
+            ("INFO", S::Info),
+            ("Warning", S::Warn),
+            ("ERR", S::Error),
+            ("critical", S::Fatal),
+            ("notice", S::Info),
+            ("alert", S::Fatal),
+            ("emerg", S::Fatal),
+            ("Trace", S::Verbose),
+        ] {
+            assert_eq!(SeverityLevel::from_name(name), level, "{name}");
+        }
+        assert_eq!(
+            SeverityLevel::from_name("Audit"),
+            S::Other("Audit".to_string())
+        );
+    }
+
+    #[test]
+    fn numeric_levels_use_the_scale() {
+        use NumericLevels as N;
+        use SeverityLevel as S;
+        for (level, scale, severity) in [
+            ("30", N::Auto, S::Info),
+            ("60", N::Auto, S::Fatal),
+            ("3", N::Auto, S::Error),
+            ("6", N::Syslog, S::Info),
+            ("0", N::Syslog, S::Fatal),
+            ("10", N::Bunyan, S::Verbose),
+            ("45", N::Bunyan, S::Error),
+            ("9", N::OpenTelemetry, S::Info),
+            ("3", N::OpenTelemetry, S::Verbose),
+            ("21", N::OpenTelemetry, S::Fatal),
+            ("warn", N::Syslog, S::Warn),
+        ] {
+            assert_eq!(SeverityLevel::parse(level, scale), severity, "{level}");
+        }
+        assert_eq!(SeverityLevel::parse("9", N::Syslog), S::Other("9".into()));
+    }
 }
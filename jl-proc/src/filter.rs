@@ -0,0 +1,413 @@
+use std::str::FromStr;
+
+use crate::{LineItem, ProcessorOptions, Query, SeverityLevel, Timestamp};
+
+// --------------------------------------------------------------------------
+
+/// A range of time to show entries from, with an inclusive start and end.
+///
+/// Entries are in the range by their timestamp. Items without a timestamp
+/// that can be parsed, like text lines, are in the range if the previous
+/// entry with a timestamp was.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::TimeRange;
+///
+/// let range = TimeRange {
+///     since: Some("2024-03-15T12:00:00Z".parse().unwrap()),
+///     until: None,
+/// };
+/// assert!(range.contains("2024-03-15T12:30:00Z".parse().unwrap()));
+/// assert!(!range.contains("2024-03-15T11:30:00Z".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeRange {
+    /// Entries before this are left out
+    pub since: Option<jiff::Timestamp>,
+    /// Entries after this are left out
+    pub until: Option<jiff::Timestamp>,
+}
+
+impl TimeRange {
+    /// Returns true if the range has no start or end.
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// Returns true if the instant is within the range.
+    pub fn contains(&self, instant: jiff::Timestamp) -> bool {
+        self.since.is_none_or(|since| since <= instant)
+            && self.until.is_none_or(|until| instant <= until)
+    }
+}
+
+/// A start or end of a time range as a user would write it: an instant, a
+/// time of day, or a time relative to now.
+///
+/// Parsed from a timestamp like `2024-03-15T12:00:00+01:00` (date-times
+/// without an offset are in UTC), a time of day like `10:30` or `10:30:15`,
+/// or a span back from now like `15m ago`, `2 hours ago` or `-2h`.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::TimeBound;
+///
+/// let now: jiff::Zoned = "2024-03-15T12:00:00+01:00[Europe/Paris]".parse().unwrap();
+/// let resolve = |s: &str| s.parse::<TimeBound>().unwrap().resolve(&now).unwrap().to_string();
+/// assert_eq!(resolve("15m ago"), "2024-03-15T10:45:00Z");
+/// assert_eq!(resolve("10:30"), "2024-03-15T09:30:00Z");
+/// assert_eq!(resolve("2024-03-15T08:00:00Z"), "2024-03-15T08:00:00Z");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub enum TimeBound {
+    /// A point in time
+    Instant(jiff::Timestamp),
+    /// A time of day, on the current day
+    TimeOfDay(jiff::civil::Time),
+    /// A span back from now
+    Ago(jiff::Span),
+}
+
+impl TimeBound {
+    /// Returns the instant of the bound, with times of day and spans taken
+    /// from `now` and in its time zone.
+    pub fn resolve(&self, now: &jiff::Zoned) -> Result<jiff::Timestamp, jiff::Error> {
+        match self {
+            TimeBound::Instant(instant) => Ok(*instant),
+            TimeBound::TimeOfDay(time) => Ok(now.with().time(*time).build()?.timestamp()),
+            TimeBound::Ago(span) => Ok(now.checked_sub(*span)?.timestamp()),
+        }
+    }
+}
+
+impl FromStr for TimeBound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        // `15m ago` and `-15m` are both negative spans
+        if let Ok(span) = s.parse::<jiff::Span>()
+            && span.is_negative()
+        {
+            return Ok(TimeBound::Ago(span.abs()));
+        }
+        // a time of day starts with the hour, not a date
+        if s.find(':').is_some_and(|i| i <= 2)
+            && let Ok(time) = s.parse::<jiff::civil::Time>()
+        {
+            return Ok(TimeBound::TimeOfDay(time));
+        }
+        match Timestamp::parse(s).instant() {
+            Some(instant) => Ok(TimeBound::Instant(instant)),
+            None => Err(format!(
+                "invalid time '{s}', expected a timestamp, a time of day like '10:30', \
+                 or a time ago like '15m ago' or '-2h'"
+            )),
+        }
+    }
+}
+
+/// A range of severity levels to show entries from, from the least to the
+/// most severe level, both inclusive.
+///
+/// Parsed from a single level, for that level and all more severe ones, or
+/// from two levels like `error..fatal`, in either order. Entries without a
+/// level or with an unknown one are only in ranges down to `verbose`.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::{LevelRange, SeverityLevel};
+///
+/// let levels: LevelRange = "warn".parse().unwrap();
+/// assert!(levels.contains(&SeverityLevel::Error));
+/// assert!(!levels.contains(&SeverityLevel::Info));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelRange {
+    /// The least severe level shown
+    pub min: SeverityLevel,
+    /// The most severe level shown
+    pub max: SeverityLevel,
+}
+
+impl LevelRange {
+    /// Returns a range of the level and all more severe levels.
+    pub fn at_least(level: SeverityLevel) -> Self {
+        Self {
+            min: level,
+            max: SeverityLevel::Fatal,
+        }
+    }
+
+    /// Returns true if the severity level is within the range.
+    pub fn contains(&self, level: &SeverityLevel) -> bool {
+        if let SeverityLevel::Other(_) = level {
+            return self.min == SeverityLevel::Verbose;
+        }
+        // more severe levels have lower numbers
+        (self.max.as_u8()..=self.min.as_u8()).contains(&level.as_u8())
+    }
+}
+
+impl FromStr for LevelRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let level = |name: &str| match SeverityLevel::from_name(name.trim()) {
+            SeverityLevel::Other(_) => Err(format!(
+                "unknown level '{name}', expected one of: fatal, error, warn, info, debug, verbose"
+            )),
+            level => Ok(level),
+        };
+        let Some((a, b)) = s.split_once("..") else {
+            return Ok(Self::at_least(level(s)?));
+        };
+        let (a, b) = (level(a)?, level(b)?);
+        Ok(if a.as_u8() < b.as_u8() {
+            Self { min: b, max: a }
+        } else {
+            Self { min: a, max: b }
+        })
+    }
+}
+
+/// Decides which items of a stream to show. Items without a timestamp follow
+/// the entry before them for the time range, and items that aren't entries
+/// are shown if the entry before them was. Errors and notices are always
+/// shown, and an encoding warning is shown with the item for its line.
+pub(crate) struct ItemFilter {
+    time_range: TimeRange,
+    levels: Option<LevelRange>,
+    query: Option<Query>,
+    in_range: bool,
+    shown: bool,
+    /// An encoding warning, held back until the item for its line is seen
+    warning: Option<LineItem>,
+}
+
+impl ItemFilter {
+    pub(crate) fn new(options: &ProcessorOptions) -> Self {
+        // items before the first timestamp are only shown without a start
+        let in_range = options.time_range.since.is_none();
+        Self {
+            time_range: options.time_range,
+            levels: options.levels.clone(),
+            query: options.query.clone(),
+            in_range,
+            shown: in_range,
+            warning: None,
+        }
+    }
+
+    /// Takes the next item, and returns the items to show for it, which are
+    /// the item with the encoding warning for its line in front of it, if
+    /// any, or nothing.
+    pub(crate) fn take(&mut self, item: LineItem) -> [Option<LineItem>; 2] {
+        if let LineItem::EncodingWarning(_) = item {
+            self.warning = Some(item);
+            return [None, None];
+        }
+        let warning = self.warning.take();
+        if self.shows(&item) {
+            [warning, Some(item)]
+        } else {
+            [None, None]
+        }
+    }
+
+    /// Returns true if the item is to be shown.
+    fn shows(&mut self, item: &LineItem) -> bool {
+        let entry = match item {
+            LineItem::Entry(entry) => entry,
+            LineItem::ReadError(..) | LineItem::ParseError(..) | LineItem::Notice(_) => {
+                return true;
+            }
+            _ => return self.shown,
+        };
+        if let Some(instant) = entry.timestamp.as_ref().and_then(|t| t.instant()) {
+            self.in_range = self.time_range.contains(instant);
+        }
+        self.shown = self.in_range
+            && self
+                .levels
+                .as_ref()
+                .is_none_or(|levels| levels.contains(&entry.severity))
+            && self.query.as_ref().is_none_or(|query| query.matches(entry));
+        self.shown
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogEntry;
+
+    fn entry(time: &str) -> LineItem {
+        LineItem::Entry(LogEntry::new(time, "info", "Hi"))
+    }
+
+    #[test]
+    fn range_bounds_are_inclusive() {
+        let range = TimeRange {
+            since: Some("2024-03-15T12:00:00Z".parse().unwrap()),
+            until: Some("2024-03-15T13:00:00Z".parse().unwrap()),
+        };
+        for (time, contained) in [
+            ("2024-03-15T11:59:59.999Z", false),
+            ("2024-03-15T12:00:00Z", true),
+            ("2024-03-15T13:00:00Z", true),
+            ("2024-03-15T13:00:00.001Z", false),
+        ] {
+            assert_eq!(range.contains(time.parse().unwrap()), contained, "{time}");
+        }
+        assert!(TimeRange::default().is_unbounded());
+    }
+
+    #[test]
+    fn items_without_timestamp_follow_the_previous_entry() {
+        let mut filter = ItemFilter::new(&ProcessorOptions {
+            time_range: TimeRange {
+                since: Some("2024-03-15T12:00:00Z".parse().unwrap()),
+                until: None,
+            },
+            ..Default::default()
+        });
+        let items = [
+            LineItem::Text(1, "banner".into()),
+            entry("2024-03-15T11:00:00Z"),
+            LineItem::Text(3, "trace".into()),
+            entry("2024-03-15T12:00:00Z"),
+            entry("not a time"),
+            LineItem::EmptyLine(6),
+        ];
+        let shown: Vec<_> = items.iter().map(|item| filter.shows(item)).collect();
+        assert_eq!(shown, [false, false, false, true, true, true]);
+    }
+
+    #[test]
+    fn parses_level_ranges() {
+        let range = |s: &str| s.parse::<LevelRange>();
+        assert_eq!(range("warn"), Ok(LevelRange::at_least(SeverityLevel::Warn)));
+        let errors = LevelRange {
+            min: SeverityLevel::Error,
+            max: SeverityLevel::Fatal,
+        };
+        assert_eq!(range("error..fatal"), Ok(errors.clone()));
+        assert_eq!(range("FATAL..err"), Ok(errors));
+        assert!(range("loud").is_err());
+        assert!(range("warn..").is_err());
+    }
+
+    #[test]
+    fn ranges_down_to_verbose_contain_unknown_levels() {
+        let unknown = SeverityLevel::Other("unknown".into());
+        assert!(LevelRange::at_least(SeverityLevel::Verbose).contains(&unknown));
+        assert!(LevelRange::at_least(SeverityLevel::Verbose).contains(&SeverityLevel::Fatal));
+        assert!(!LevelRange::at_least(SeverityLevel::Debug).contains(&unknown));
+        let quiet = LevelRange {
+            min: SeverityLevel::Verbose,
+            max: SeverityLevel::Debug,
+        };
+        assert!(quiet.contains(&unknown));
+        assert!(!quiet.contains(&SeverityLevel::Info));
+    }
+
+    #[test]
+    fn items_follow_the_level_of_the_previous_entry() {
+        let mut filter = ItemFilter::new(&ProcessorOptions {
+            levels: Some(LevelRange::at_least(SeverityLevel::Warn)),
+            ..Default::default()
+        });
+        let items = [
+            LineItem::Text(1, "banner".into()),
+            LineItem::Entry(LogEntry::new("2024-03-15T12:00:00Z", "info", "Hi")),
+            LineItem::Text(3, "trace".into()),
+            LineItem::Entry(LogEntry::new("2024-03-15T12:00:01Z", "error", "Oops")),
+            LineItem::Text(5, "trace".into()),
+            LineItem::Entry(LogEntry::new("2024-03-15T12:00:02Z", "", "No level")),
+        ];
+        let shown: Vec<_> = items.iter().map(|item| filter.shows(item)).collect();
+        assert_eq!(shown, [true, false, false, true, true, false]);
+    }
+
+    #[test]
+    fn errors_are_always_shown() {
+        let mut filter = ItemFilter::new(&ProcessorOptions {
+            levels: Some(LevelRange::at_least(SeverityLevel::Error)),
+            time_range: TimeRange {
+                since: Some("2024-03-15T12:00:00Z".parse().unwrap()),
+                until: None,
+            },
+            ..Default::default()
+        });
+        let broken = serde_json::from_str::<serde_json::Value>("{\"broken\":").unwrap_err();
+        let items = [
+            LineItem::ParseError(1, broken),
+            LineItem::Entry(LogEntry::new("2024-03-15T12:00:00Z", "info", "Hi")),
+            LineItem::ReadError(3, std::io::Error::other("disk failed")),
+            LineItem::Text(4, "trace".into()),
+        ];
+        let shown: Vec<_> = items.iter().map(|item| filter.shows(item)).collect();
+        assert_eq!(shown, [true, false, true, false]);
+    }
+
+    #[test]
+    fn encoding_warnings_go_with_the_item_for_their_line() {
+        let mut filter = ItemFilter::new(&ProcessorOptions {
+            levels: Some(LevelRange::at_least(SeverityLevel::Error)),
+            ..Default::default()
+        });
+        let items = [
+            LineItem::Entry(LogEntry::new("2024-03-15T12:00:00Z", "error", "Oops")),
+            LineItem::EncodingWarning(2),
+            LineItem::Entry(LogEntry::new("2024-03-15T12:00:01Z", "info", "Hi \u{FFFD}")),
+            LineItem::EncodingWarning(3),
+            LineItem::Entry(LogEntry::new(
+                "2024-03-15T12:00:02Z",
+                "error",
+                "Oops \u{FFFD}",
+            )),
+        ];
+        let shown: Vec<_> = items
+            .into_iter()
+            .flat_map(|item| filter.take(item))
+            .flatten()
+            .map(|item| match item {
+                LineItem::Entry(entry) => entry.message,
+                LineItem::EncodingWarning(line_no) => format!("warning {line_no}"),
+                _ => "other".into(),
+            })
+            .collect();
+        assert_eq!(shown, ["Oops", "warning 3", "Oops \u{FFFD}"]);
+    }
+
+    #[test]
+    fn resolves_time_bounds() {
+        let now: jiff::Zoned = "2024-03-15T00:20:00-04:00[America/New_York]"
+            .parse()
+            .unwrap();
+        let resolve = |s: &str| {
+            let bound: TimeBound = s.parse().unwrap();
+            bound.resolve(&now).unwrap().to_string()
+        };
+        for (bound, instant) in [
+            ("2024-03-14T08:00:00+01:00", "2024-03-14T07:00:00Z"),
+            ("2024-03-14T08:00", "2024-03-14T08:00:00Z"),
+            ("1710400000", "2024-03-14T07:06:40Z"),
+            ("10:30", "2024-03-15T14:30:00Z"),
+            ("00:05:30.5", "2024-03-15T04:05:30.5Z"),
+            ("15m ago", "2024-03-15T04:05:00Z"),
+            (" -2h ", "2024-03-15T02:20:00Z"),
+            ("1 day ago", "2024-03-14T04:20:00Z"),
+            ("1h 30m ago", "2024-03-15T02:50:00Z"),
+        ] {
+            assert_eq!(resolve(bound), instant, "{bound}");
+        }
+        for bad in ["", "15m", "25:00", "yesterday", "10"] {
+            assert!(bad.parse::<TimeBound>().is_err(), "{bad}");
+        }
+    }
+}
@@ -1,6 +1,60 @@
-use std::{collections::HashMap, io::Write};
+use std::{collections::HashMap, io::Write, str::FromStr};
 
-use crate::{LogEntry, ValuePrinter, ValuePrinterConfig, ansi_color};
+use jiff::{
+    Zoned,
+    tz::{Offset, TimeZone},
+};
+
+use regex::Regex;
+
+use crate::{
+    LogEntry, Timestamp, ValuePrinter, ValuePrinterConfig, ansi_color,
+    grep::Highlighter,
+    timestamp::{format_date_time, format_duration, format_time_short},
+};
+
+// --------------------------------------------------------------------------
+
+/// How the timestamps of log entries are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TimeDisplay {
+    /// The time of day, `HH:MM:SS.mmm`
+    #[default]
+    Time,
+    /// The date and time with offset, `YYYY-MM-DD HH:MM:SS.mmm+HH:MM`
+    DateTime,
+    /// The time since the first entry, `+HH:MM:SS.mmm`
+    Relative,
+    /// The time since the previous entry, `+HH:MM:SS.mmm`
+    Delta,
+}
+
+impl TimeDisplay {
+    /// The width of a displayed timestamp.
+    fn width(&self) -> usize {
+        match self {
+            TimeDisplay::Time => 12,
+            TimeDisplay::DateTime => 29,
+            TimeDisplay::Relative | TimeDisplay::Delta => 13,
+        }
+    }
+}
+
+impl FromStr for TimeDisplay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "time" | "short" => Ok(TimeDisplay::Time),
+            "full" | "datetime" => Ok(TimeDisplay::DateTime),
+            "relative" => Ok(TimeDisplay::Relative),
+            "delta" => Ok(TimeDisplay::Delta),
+            other => Err(format!(
+                "unknown time display '{other}', expected one of: time, full, relative, delta"
+            )),
+        }
+    }
+}
 
 // --------------------------------------------------------------------------
 
@@ -11,7 +65,19 @@ pub struct LogEntryFormatter<W: Write> {
     level_table: [&'static str; 7],
     value_printer: ValuePrinter,
     eol: &'static str,
+    text_format: &'static str,
+    text_marker: String,
+    label_colors: &'static [&'static str],
+    label_width: usize,
     skip_extras: bool,
+    time_display: TimeDisplay,
+    time_zone: Option<TimeZone>,
+    first_instant: Option<jiff::Timestamp>,
+    previous_instant: Option<jiff::Timestamp>,
+    current_source: Option<String>,
+    line_prefix: String,
+    use_color: bool,
+    highlighter: Option<Highlighter>,
 }
 
 impl<W: Write> LogEntryFormatter<W> {
@@ -22,14 +88,16 @@ impl<W: Write> LogEntryFormatter<W> {
 
     /// Creates a new `LogEntryFormatter` with options for skipping extras.
     pub fn with_options(use_color: bool, skip_extras: bool, writer: W) -> Self {
-        let (timestamp_format, level_table, eol) = if use_color {
+        let (timestamp_format, level_table, eol, text_format, label_colors) = if use_color {
             (
                 ansi_color!(fg:39),
                 DEFAULT_LEVEL_TABLE_COLOR,
                 concat!(ansi_color!(), "\n"),
+                ansi_color!(fg: 244),
+                LABEL_COLORS,
             )
         } else {
-            ("", DEFAULT_LEVEL_TABLE, "\n")
+            ("", DEFAULT_LEVEL_TABLE, "\n", "", &[""] as &[&str])
         };
 
         let value_printer = ValuePrinter::new(ValuePrinterConfig {
@@ -43,32 +111,114 @@ impl<W: Write> LogEntryFormatter<W> {
             writer,
             value_printer,
             eol,
+            text_format,
+            text_marker: String::new(),
+            label_colors,
+            label_width: 0,
             timestamp_format,
             skip_extras,
+            time_display: TimeDisplay::default(),
+            time_zone: None,
+            first_instant: None,
+            previous_instant: None,
+            current_source: None,
+            line_prefix: String::new(),
+            use_color,
+            highlighter: None,
         }
     }
 
+    /// Sets a marker to show in front of plain text lines.
+    pub fn with_text_marker(mut self, marker: impl Into<String>) -> Self {
+        self.text_marker = marker.into();
+        self
+    }
+
+    /// Sets the width labels are padded to, when the labels to show are known
+    /// up front. Wider labels widen the column.
+    pub fn with_label_width(mut self, width: usize) -> Self {
+        self.label_width = width;
+        self
+    }
+
+    /// Highlights the matches of a pattern in messages and extras values.
+    pub fn with_highlight(mut self, regex: Regex) -> Self {
+        self.value_printer = self.value_printer.with_highlight(regex.clone());
+        self.highlighter = Some(Highlighter::new(regex, self.use_color));
+        self
+    }
+
+    /// Sets how timestamps are displayed, and the time zone they are shown
+    /// in. Without a time zone, timestamps are shown in the offset they were
+    /// written in.
+    pub fn with_time_display(mut self, display: TimeDisplay, time_zone: Option<TimeZone>) -> Self {
+        self.time_display = display;
+        self.time_zone = time_zone;
+        self
+    }
+
     pub fn format_session_start(&mut self, entry: &LogEntry) -> std::io::Result<()> {
-        writeln!(
-            self.writer,
-            "------------ New session started {}{} {}",
-            self.timestamp_format,
-            &entry.timestamp[..10],
-            &entry.timestamp[11..],
-        )?;
+        match &entry.timestamp {
+            Some(timestamp) => writeln!(
+                self.writer,
+                "------------ New session started {}{}",
+                self.timestamp_format,
+                timestamp.date_time(),
+            )?,
+            None => writeln!(self.writer, "------------ New session started")?,
+        }
         Ok(())
     }
 
+    /// Sets a prefix for the line numbers in warnings and errors, like
+    /// `tail+` for line numbers counted from somewhere in the middle of the
+    /// input. Can be changed between sources.
+    pub fn set_line_prefix(&mut self, prefix: impl Into<String>) {
+        self.line_prefix = prefix.into();
+    }
+
+    /// Writes a header with the name of the source, if it isn't the source
+    /// of the previous header. Headers after the first are preceded by an
+    /// empty line.
+    pub fn format_source_header(&mut self, source: &str) -> std::io::Result<()> {
+        if self.current_source.as_deref() == Some(source) {
+            return Ok(());
+        }
+        if self.current_source.replace(source.to_string()).is_some() {
+            writeln!(self.writer)?;
+        }
+        write!(
+            self.writer,
+            "{}==> {source} <=={}",
+            self.timestamp_format, self.eol
+        )
+    }
+
     /// Formats a single log entry and writes it to the writer.
     pub fn format_entry(&mut self, entry: &LogEntry) -> std::io::Result<()> {
+        if let Some(label) = &entry.label {
+            // labels are padded to the widest one seen so far
+            self.label_width = self.label_width.max(label.chars().count());
+            write!(
+                self.writer,
+                "{}{label:width$} ",
+                self.label_color(label),
+                width = self.label_width
+            )?;
+        }
+        let timestamp = self.display_timestamp(entry);
         write!(
             self.writer,
-            "{}{}",
+            "{}{:width$}",
             self.timestamp_format,
-            entry.timestamp_short()
+            timestamp,
+            width = self.time_display.width()
         )?;
         write!(self.writer, "{}", self.level_table[entry.level().as_u8()])?;
-        write!(self.writer, "{}", entry.message)?;
+        match &self.highlighter {
+            Some(highlighter) => highlighter.write(&mut self.writer, &entry.message)?,
+            None => write!(self.writer, "{}", entry.message)?,
+        }
         write!(self.writer, "{}", self.eol)?;
         if !self.skip_extras {
             self.format_extras_collection(&entry.extras)?;
@@ -76,6 +226,63 @@ impl<W: Write> LogEntryFormatter<W> {
         Ok(())
     }
 
+    /// Formats a line of plain text, like the output of a panic, and writes
+    /// it to the writer.
+    pub fn format_text(&mut self, text: &str) -> std::io::Result<()> {
+        write!(
+            self.writer,
+            "{}{}{}{}",
+            self.text_format, self.text_marker, text, self.eol
+        )
+    }
+
+    /// Returns the color for a label, which is the same every time the label
+    /// is shown.
+    fn label_color(&self, label: &str) -> &'static str {
+        let hash = label.bytes().fold(0usize, |hash, b| {
+            hash.wrapping_mul(31).wrapping_add(b as usize)
+        });
+        self.label_colors[hash % self.label_colors.len()]
+    }
+
+    /// Returns the timestamp of an entry as it should be displayed.
+    fn display_timestamp(&mut self, entry: &LogEntry) -> String {
+        let Some(timestamp) = &entry.timestamp else {
+            return String::new();
+        };
+        let Some(instant) = timestamp.instant() else {
+            return timestamp.as_str().to_string();
+        };
+        let previous = self.previous_instant.replace(instant);
+        match self.time_display {
+            TimeDisplay::Time => format_time_short(&self.to_zoned(timestamp, instant)),
+            TimeDisplay::DateTime => format_date_time(&self.to_zoned(timestamp, instant)),
+            TimeDisplay::Relative => {
+                let first = *self.first_instant.get_or_insert(instant);
+                format_duration(instant.duration_since(first))
+            }
+            TimeDisplay::Delta => {
+                format_duration(instant.duration_since(previous.unwrap_or(instant)))
+            }
+        }
+    }
+
+    /// Returns the instant of a timestamp in the display time zone, or the
+    /// offset of the timestamp if no time zone is set.
+    fn to_zoned(&self, timestamp: &Timestamp, instant: jiff::Timestamp) -> Zoned {
+        let time_zone = match &self.time_zone {
+            Some(time_zone) => time_zone.clone(),
+            None => TimeZone::fixed(timestamp.offset().unwrap_or(Offset::UTC)),
+        };
+        instant.to_zoned(time_zone)
+    }
+
+    /// Writes a line between groups of matches with their context, when
+    /// entries between them are left out.
+    pub fn format_context_separator(&mut self) -> std::io::Result<()> {
+        write!(self.writer, "{}--{}", self.text_format, self.eol)
+    }
+
     /// Formats a number of empty lines and writes it to the writer.
     pub fn format_empty_lines(&mut self, n: usize, source: &str) -> std::io::Result<()> {
         writeln!(self.writer, "{source}: {n} empty lines skipped -----------")
@@ -91,10 +298,23 @@ impl<W: Write> LogEntryFormatter<W> {
                 extra.clone().into_iter().collect();
             self.value_printer
                 .print_object_contents(&mut self.writer, &obj, 2)?;
+            // compact objects are written on a single line that isn't ended
+            if self.value_printer.should_format_compact_object(&obj) {
+                writeln!(self.writer)?;
+            }
         }
         Ok(())
     }
 
+    /// Formats a warning about invalid UTF-8 and writes it to the writer.
+    pub fn format_encoding_warning(&mut self, line_no: usize, source: &str) -> std::io::Result<()> {
+        writeln!(
+            self.writer,
+            "{source}({}{line_no}): Encoding warning, invalid UTF-8 replaced",
+            self.line_prefix
+        )
+    }
+
     /// Formats a read error and writes it to the writer.
     pub fn format_read_error(
         &mut self,
@@ -102,7 +322,21 @@ impl<W: Write> LogEntryFormatter<W> {
         source: &str,
         error: std::io::Error,
     ) -> std::io::Result<()> {
-        writeln!(self.writer, "{source}({line_no}): Read error {error}")
+        writeln!(
+            self.writer,
+            "{source}({}{line_no}): Read error {error}",
+            self.line_prefix
+        )
+    }
+
+    /// Formats a note about the source itself and writes it to the writer.
+    pub fn format_notice(&mut self, source: &str, notice: &str) -> std::io::Result<()> {
+        writeln!(self.writer, "{source}: {notice}")
+    }
+
+    /// Flushes the writer, so the output so far is shown right away.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
     }
 
     /// Formats a parse error and writes it to the writer.
@@ -112,7 +346,11 @@ impl<W: Write> LogEntryFormatter<W> {
         source: &str,
         error: serde_json::Error,
     ) -> std::io::Result<()> {
-        writeln!(self.writer, "{source}({line_no}): Parse error {error}")
+        writeln!(
+            self.writer,
+            "{source}({}{line_no}): Parse error {error}",
+            self.line_prefix
+        )
     }
 }
 
@@ -128,6 +366,18 @@ const DEFAULT_LEVEL_TABLE: [&str; 7] = [
     " [unk] ", // Other
 ];
 
+// Colors for labels, picked to be easy to tell apart on a dark background
+const LABEL_COLORS: &[&str] = &[
+    ansi_color!(fg: 208), // Orange
+    ansi_color!(fg: 141), // Lavender
+    ansi_color!(fg: 43),  // Turquoise
+    ansi_color!(fg: 205), // Hot Pink
+    ansi_color!(fg: 148), // Lime
+    ansi_color!(fg: 75),  // Sky Blue
+    ansi_color!(fg: 222), // Sand
+    ansi_color!(fg: 167), // Indian Red
+];
+
 // See color table here https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit
 //
 // Color names are from https://colornamer.robertcooper.me/
@@ -136,7 +386,171 @@ const DEFAULT_LEVEL_TABLE_COLOR: [&str; 7] = [
     concat!(ansi_color!(fg: 9), " [err] "),         // Error -> Red
     concat!(ansi_color!(fg: 11), " [wrn] "),        // Warning -> Yellow
     concat!(ansi_color!(fg: 2), " [inf] ", ansi_color!(fg: 254)), // Info -> Titanium White
-    concat!(ansi_color!(fg: 6), " [vrb] "),         // Verbose -> Teal
     concat!(ansi_color!(fg: 27), " [dbg] "),        // Debug -> Bright Blue
+    concat!(ansi_color!(fg: 6), " [vrb] "),         // Verbose -> Teal
     concat!(ansi_color!(fg: 5), " [unk] "),         // Other -> Purple
 ];
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeverityLevel;
+
+    fn format_all(
+        display: TimeDisplay,
+        time_zone: Option<TimeZone>,
+        timestamps: &[&str],
+    ) -> Vec<String> {
+        let mut output = Vec::new();
+        let mut formatter =
+            LogEntryFormatter::new(false, &mut output).with_time_display(display, time_zone);
+        for timestamp in timestamps {
+            formatter
+                .format_entry(&LogEntry::new(*timestamp, "info", "m"))
+                .unwrap();
+        }
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| line.trim_end_matches(" [inf] m").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn level_tables_are_in_severity_order() {
+        for (level, tag, color) in [
+            (SeverityLevel::Fatal, " [ftl] ", ansi_color!(fg: 11, bg: 9)),
+            (SeverityLevel::Error, " [err] ", ansi_color!(fg: 9)),
+            (SeverityLevel::Warn, " [wrn] ", ansi_color!(fg: 11)),
+            (SeverityLevel::Info, " [inf] ", ansi_color!(fg: 2)),
+            (SeverityLevel::Debug, " [dbg] ", ansi_color!(fg: 27)),
+            (SeverityLevel::Verbose, " [vrb] ", ansi_color!(fg: 6)),
+            (
+                SeverityLevel::Other("trace".into()),
+                " [unk] ",
+                ansi_color!(fg: 5),
+            ),
+        ] {
+            assert_eq!(DEFAULT_LEVEL_TABLE[level.as_u8()], tag);
+            let colored = DEFAULT_LEVEL_TABLE_COLOR[level.as_u8()];
+            assert!(colored.starts_with(&format!("{color}{tag}")), "{level:?}");
+        }
+    }
+
+    #[test]
+    fn can_display_time_in_other_zones() {
+        let timestamps = ["2024-03-15T12:34:56.123+01:00"];
+        assert_eq!(
+            format_all(TimeDisplay::Time, None, &timestamps),
+            ["12:34:56.123"]
+        );
+        assert_eq!(
+            format_all(TimeDisplay::DateTime, None, &timestamps),
+            ["2024-03-15 12:34:56.123+01:00"]
+        );
+        assert_eq!(
+            format_all(TimeDisplay::Time, Some(TimeZone::UTC), &timestamps),
+            ["11:34:56.123"]
+        );
+        let new_york = TimeZone::get("America/New_York").unwrap();
+        assert_eq!(
+            format_all(TimeDisplay::DateTime, Some(new_york), &timestamps),
+            ["2024-03-15 07:34:56.123-04:00"]
+        );
+    }
+
+    #[test]
+    fn shows_labels_in_front() {
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output);
+        let mut entry = LogEntry::new("2024-03-15T12:34:56.123Z", "info", "Hi");
+        entry.label = Some("web-1".into());
+        formatter.format_entry(&entry).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "web-1 12:34:56.123 [inf] Hi\n"
+        );
+
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output).with_label_width(8);
+        formatter.format_entry(&entry).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "web-1    12:34:56.123 [inf] Hi\n"
+        );
+    }
+
+    #[test]
+    fn can_display_relative_and_delta_time() {
+        let timestamps = [
+            "2024-03-15T12:34:56.123Z",
+            "2024-03-15T12:34:57.000Z",
+            "not a time",
+            "2024-03-15T13:35:00.000+01:00",
+        ];
+        assert_eq!(
+            format_all(TimeDisplay::Relative, None, &timestamps),
+            [
+                "+00:00:00.000",
+                "+00:00:00.877",
+                "not a time   ",
+                "+00:00:03.877"
+            ]
+        );
+        assert_eq!(
+            format_all(TimeDisplay::Delta, None, &timestamps),
+            [
+                "+00:00:00.000",
+                "+00:00:00.877",
+                "not a time   ",
+                "+00:00:03.000"
+            ]
+        );
+    }
+
+    #[test]
+    fn shows_line_prefixes_in_errors() {
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output);
+        formatter.format_encoding_warning(3, "app.log").unwrap();
+        formatter.set_line_prefix("tail+");
+        formatter.format_encoding_warning(3, "app.log").unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "app.log(3): Encoding warning, invalid UTF-8 replaced\n\
+app.log(tail+3): Encoding warning, invalid UTF-8 replaced\n"
+        );
+    }
+
+    #[test]
+    fn ends_the_line_after_compact_extras() {
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output);
+        let mut entry = LogEntry::new("2024-03-15T12:34:56.123Z", "info", "Hi");
+        entry.extras.insert("status".into(), 200.into());
+        formatter.format_entry(&entry).unwrap();
+        formatter.format_entry(&entry).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "12:34:56.123 [inf] Hi\n    status: 200\n\
+12:34:56.123 [inf] Hi\n    status: 200\n"
+        );
+    }
+
+    #[test]
+    fn highlights_matches_in_messages_and_extras() {
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(true, &mut output)
+            .with_highlight(Regex::new("(?i)timeout").unwrap());
+        let mut entry = LogEntry::new("2024-03-15T12:00:00Z", "error", "Timeout reached");
+        entry
+            .extras
+            .insert("error".into(), "connection timeout".into());
+        formatter.format_entry(&entry).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\x1b[7mTimeout\x1b[27m reached"));
+        assert!(output.contains("connection \x1b[7mtimeout\x1b[27m\""));
+    }
+}
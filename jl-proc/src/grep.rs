@@ -0,0 +1,337 @@
+use std::{collections::VecDeque, io::Write};
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{LineItem, LogEntry, csi};
+
+// --------------------------------------------------------------------------
+
+/// A search for entries with a pattern in their message or extras values,
+/// like `grep`, with entries around the matches shown as context.
+///
+/// Items that aren't entries, like stack traces, go with the entry before
+/// them, and context is counted in entries. Read and parse errors are always
+/// shown.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::{Grep, LogEntry};
+/// use regex::Regex;
+///
+/// let grep = Grep::new(Regex::new("time(d )?out").unwrap());
+/// let mut entry = LogEntry::new("2024-03-15T12:00:00Z", "error", "Request failed");
+/// assert!(!grep.matches(&entry));
+/// entry.extras.insert("error".into(), "connection timed out".into());
+/// assert!(grep.matches(&entry));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Grep {
+    /// The pattern to search for
+    pub regex: Regex,
+    /// If true, show the entries that don't match instead
+    pub invert: bool,
+    /// How many entries to show before each match
+    pub before: usize,
+    /// How many entries to show after each match
+    pub after: usize,
+}
+
+impl Grep {
+    /// Creates a search for the pattern, without context.
+    pub fn new(regex: Regex) -> Self {
+        Self {
+            regex,
+            invert: false,
+            before: 0,
+            after: 0,
+        }
+    }
+
+    /// Returns true if the pattern is found in the message or an extras
+    /// value of the entry, or isn't found if the search is inverted.
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        let found = self.regex.is_match(&entry.message)
+            || entry.extras.values().any(|value| self.is_in(value));
+        found != self.invert
+    }
+
+    /// Returns true if the pattern is found in the value, or in any value
+    /// nested in it.
+    fn is_in(&self, value: &Value) -> bool {
+        match value {
+            Value::String(s) => self.regex.is_match(s),
+            Value::Array(items) => items.iter().any(|item| self.is_in(item)),
+            Value::Object(map) => map.values().any(|value| self.is_in(value)),
+            other => self.regex.is_match(&other.to_string()),
+        }
+    }
+}
+
+/// Picks the items to show for a search, with the context around matches.
+pub(crate) struct GrepContext<'s> {
+    grep: Grep,
+    /// The latest entries that weren't shown, with the items that follow
+    /// them, kept to show before the next match
+    before: VecDeque<Vec<(&'s str, LineItem)>>,
+    /// How many more entries to show after the last match
+    after_left: usize,
+    /// If true, the items after the current entry are shown
+    showing: bool,
+    /// If true, items were left out since the last one shown
+    skipped: bool,
+    shown_any: bool,
+    /// An encoding warning, held back to go with the item for its line
+    warning: Option<(&'s str, LineItem)>,
+}
+
+impl<'s> GrepContext<'s> {
+    pub(crate) fn new(grep: Grep) -> Self {
+        Self {
+            before: VecDeque::with_capacity(grep.before),
+            grep,
+            after_left: 0,
+            showing: false,
+            skipped: false,
+            shown_any: false,
+            warning: None,
+        }
+    }
+
+    /// Takes the next item, and adds the items to show so far to `shown`,
+    /// with `None` for a separator between groups of matches with their
+    /// context.
+    pub(crate) fn push(
+        &mut self,
+        source: &'s str,
+        item: LineItem,
+        shown: &mut Vec<Option<(&'s str, LineItem)>>,
+    ) {
+        if let LineItem::EncodingWarning(_) = item {
+            self.warning = Some((source, item));
+            return;
+        }
+        let is_error = matches!(
+            item,
+            LineItem::ReadError(..) | LineItem::ParseError(..) | LineItem::Notice(_)
+        );
+        let mut line: Vec<_> = self.warning.take().into_iter().collect();
+        line.push((source, item));
+        let Some((_, LineItem::Entry(entry))) = line.last() else {
+            if self.showing {
+                shown.extend(line.into_iter().map(Some));
+            } else if is_error {
+                // the entries kept for context would come after the error
+                self.skipped |= !self.before.is_empty();
+                self.before.clear();
+                if self.shown_any && self.skipped {
+                    shown.push(None);
+                }
+                shown.extend(line.into_iter().map(Some));
+                self.skipped = false;
+                self.shown_any = true;
+            } else if let Some(record) = self.before.back_mut() {
+                record.extend(line);
+            } else {
+                self.skipped = true;
+            }
+            return;
+        };
+        if self.grep.matches(entry) {
+            self.after_left = self.grep.after;
+        } else if self.after_left > 0 {
+            self.after_left -= 1;
+        } else {
+            self.showing = false;
+            if self.before.len() == self.grep.before {
+                self.skipped |= self.before.pop_front().is_some() || self.grep.before == 0;
+            }
+            if self.grep.before > 0 {
+                self.before.push_back(line);
+            }
+            return;
+        }
+        if self.shown_any && self.skipped {
+            shown.push(None);
+        }
+        for record in self.before.drain(..) {
+            shown.extend(record.into_iter().map(Some));
+        }
+        shown.extend(line.into_iter().map(Some));
+        self.showing = true;
+        self.skipped = false;
+        self.shown_any = true;
+    }
+}
+
+/// Writes text with the matches of a pattern in it shown in inverse video,
+/// which keeps the colors the text is written in.
+#[derive(Debug, Clone)]
+pub(crate) struct Highlighter {
+    regex: Regex,
+    start: &'static str,
+    end: &'static str,
+}
+
+impl Highlighter {
+    pub(crate) fn new(regex: Regex, use_color: bool) -> Self {
+        let (start, end) = if use_color {
+            (csi!("7", ""), csi!("27", ""))
+        } else {
+            ("", "")
+        };
+        Self { regex, start, end }
+    }
+
+    pub(crate) fn write(&self, writer: &mut impl Write, text: &str) -> std::io::Result<()> {
+        let mut written = 0;
+        for found in self.regex.find_iter(text).filter(|m| !m.is_empty()) {
+            write!(
+                writer,
+                "{}{}{}{}",
+                &text[written..found.start()],
+                self.start,
+                found.as_str(),
+                self.end
+            )?;
+            written = found.end();
+        }
+        write!(writer, "{}", &text[written..])
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn entry(message: &str) -> LineItem {
+        LineItem::Entry(LogEntry::new("2024-03-15T12:00:00Z", "info", message))
+    }
+
+    fn grep(items: Vec<LineItem>, before: usize, after: usize) -> Vec<String> {
+        let grep = Grep {
+            before,
+            after,
+            ..Grep::new(Regex::new("match").unwrap())
+        };
+        let mut context = GrepContext::new(grep);
+        let mut shown = Vec::new();
+        for item in items {
+            context.push("test.log", item, &mut shown);
+        }
+        shown
+            .into_iter()
+            .map(|grepped| match grepped {
+                Some((_, LineItem::Entry(entry))) => entry.message,
+                Some((_, LineItem::Text(_, text))) => text,
+                Some((_, LineItem::ReadError(line_no, _) | LineItem::ParseError(line_no, _))) => {
+                    format!("error {line_no}")
+                }
+                Some((_, LineItem::EncodingWarning(line_no))) => format!("warning {line_no}"),
+                Some(_) => "other".into(),
+                None => "--".into(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_messages_and_extras_values() {
+        let grep = Grep::new(Regex::new("^42$|timeout").unwrap());
+        let mut entry = LogEntry::new("2024-03-15T12:00:00Z", "error", "Request failed");
+        assert!(!grep.matches(&entry));
+        entry.extras.insert("user_id".into(), json!(42));
+        assert!(grep.matches(&entry));
+        entry.extras.clear();
+        entry
+            .extras
+            .insert("error".into(), json!({"causes": ["dns", "timeout"]}));
+        assert!(grep.matches(&entry));
+        entry.extras.insert("timeout".into(), json!(null));
+        let inverted = Grep {
+            invert: true,
+            ..Grep::new(Regex::new("timeout").unwrap())
+        };
+        assert!(!inverted.matches(&entry));
+        assert!(inverted.matches(&LogEntry::new("", "info", "Hi")));
+    }
+
+    #[test]
+    fn shows_context_around_matches() {
+        let items = || {
+            vec![
+                entry("1"),
+                entry("2"),
+                entry("3 match"),
+                LineItem::Text(4, "  trace".into()),
+                entry("5"),
+                entry("6"),
+                entry("7"),
+                entry("8 match"),
+                entry("9 match"),
+                entry("10"),
+            ]
+        };
+        assert_eq!(
+            grep(items(), 0, 0),
+            ["3 match", "  trace", "--", "8 match", "9 match"]
+        );
+        assert_eq!(
+            grep(items(), 1, 1),
+            [
+                "2", "3 match", "  trace", "5", "--", "7", "8 match", "9 match", "10"
+            ]
+        );
+        assert_eq!(
+            grep(items(), 2, 0),
+            [
+                "1", "2", "3 match", "  trace", "--", "6", "7", "8 match", "9 match"
+            ]
+        );
+    }
+
+    #[test]
+    fn shows_errors_and_keeps_encoding_warnings_with_their_line() {
+        let broken = || serde_json::from_str::<Value>("{\"broken\":").unwrap_err();
+        let items = vec![
+            entry("1"),
+            LineItem::ParseError(2, broken()),
+            entry("3"),
+            entry("4"),
+            LineItem::EncodingWarning(5),
+            entry("5 match"),
+            LineItem::EncodingWarning(6),
+            entry("6"),
+            entry("7"),
+            LineItem::ReadError(8, std::io::Error::other("disk failed")),
+        ];
+        assert_eq!(
+            grep(items, 1, 0),
+            [
+                "error 2",
+                "--",
+                "4",
+                "warning 5",
+                "5 match",
+                "--",
+                "error 8"
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_matches() {
+        let highlight = |use_color| {
+            let mut output = Vec::new();
+            Highlighter::new(Regex::new("o+|x*").unwrap(), use_color)
+                .write(&mut output, "foo bar boo")
+                .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(highlight(true), "f\x1b[7moo\x1b[27m bar b\x1b[7moo\x1b[27m");
+        assert_eq!(highlight(false), "foo bar boo");
+    }
+}
@@ -1,20 +1,131 @@
 // --------------------------------------------------------------------------
 
-use std::io::{BufRead, Lines};
+use std::{borrow::Cow, collections::BTreeMap, io::BufRead, str::FromStr};
 
-use crate::LogEntry;
+use serde_json::{Map, Value};
+
+use crate::{FieldSchema, LogEntry, PrefixExtractor, PrefixedLine, Timestamp, logfmt};
 
 // --------------------------------------------------------------------------
 
 pub enum LineItem {
     Entry(LogEntry),
+    /// A line that isn't a json object, like the output of a panic.
+    Text(usize, String),
     EmptyLine(usize),
+    /// The line contained invalid UTF-8, which was replaced. The item for the
+    /// decoded line follows this one.
+    EncodingWarning(usize),
     ReadError(usize, std::io::Error),
     ParseError(usize, serde_json::Error),
+    /// A note about the input itself rather than one of its lines, like a
+    /// followed file being replaced or truncated.
+    Notice(String),
+}
+
+/// The format of the lines read by a [`LogEntryIterator`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum InputFormat {
+    /// A json object per line
+    #[default]
+    Json,
+    /// `key=value` pairs per line, like `ts=... level=info msg="Hi"`
+    Logfmt,
+    /// Json for lines starting with `{`, logfmt for all others
+    Auto,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(InputFormat::Json),
+            "logfmt" => Ok(InputFormat::Logfmt),
+            "auto" => Ok(InputFormat::Auto),
+            other => Err(format!(
+                "unknown input format '{other}', expected one of: json, logfmt, auto"
+            )),
+        }
+    }
+}
+
+/// The byte that separates the records read by a [`LogEntryIterator`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordDelimiter {
+    /// A record per line, ending in `\n` or `\r\n`
+    Newline,
+    /// Records starting with the ASCII record separator `0x1E`, as in RFC
+    /// 7464 json text sequences
+    RecordSeparator,
+    /// Records ending in a NUL byte
+    Nul,
+    /// Records ending in any other byte
+    Byte(u8),
+}
+
+impl RecordDelimiter {
+    /// Returns the byte that separates the records.
+    pub fn byte(&self) -> u8 {
+        match self {
+            RecordDelimiter::Newline => b'\n',
+            RecordDelimiter::RecordSeparator => 0x1E,
+            RecordDelimiter::Nul => 0,
+            RecordDelimiter::Byte(byte) => *byte,
+        }
+    }
+
+    /// Picks the record separator if the input starts with `0x1E`, and
+    /// newlines otherwise.
+    fn detect(reader: &mut impl BufRead) -> Self {
+        match reader.fill_buf() {
+            Ok([0x1E, ..]) => RecordDelimiter::RecordSeparator,
+            _ => RecordDelimiter::Newline,
+        }
+    }
+
+    /// Removes the delimiter from the end of a record. Records separated by
+    /// anything but newlines also have surrounding whitespace removed, like
+    /// the newline ending each record of a json text sequence.
+    fn trim<'a>(&self, record: &'a [u8]) -> &'a [u8] {
+        match self.byte() {
+            b'\n' => trim_eol(record),
+            byte => record.strip_suffix(&[byte]).unwrap_or(record).trim_ascii(),
+        }
+    }
+}
+
+impl FromStr for RecordDelimiter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newline" | "lf" => Ok(RecordDelimiter::Newline),
+            "rs" | "json-seq" => Ok(RecordDelimiter::RecordSeparator),
+            "nul" => Ok(RecordDelimiter::Nul),
+            other if other.len() == 1 && other.is_ascii() => {
+                Ok(RecordDelimiter::Byte(other.as_bytes()[0]))
+            }
+            other => Err(format!(
+                "unknown record delimiter '{other}', expected one of: newline, rs, nul, or a single character"
+            )),
+        }
+    }
 }
 
 /// An iterator over log entries from a buffered reader.
 ///
+/// Lines are decoded lossily, so invalid UTF-8 only results in an
+/// [`LineItem::EncodingWarning`] for that line. Iteration stops after a read
+/// error.
+///
+/// Records are lines by default, or json text sequences if the input starts
+/// with the record separator `0x1E`. With any other delimiter than newlines,
+/// the line number of an item is the number of the record, and empty records
+/// are skipped. A line split over several lines, like partial CRI lines or
+/// long lines in Docker json-file logs, is joined and has the number of its
+/// last line.
+///
 /// ### Examples
 /// ```no_run
 /// use std::io::{BufReader, BufRead};
@@ -23,21 +134,209 @@ pub enum LineItem {
 /// let entries: Vec<_> = LogEntryIterator::from_buf_reader(input).collect();
 /// ```
 pub struct LogEntryIterator<B: BufRead> {
-    lines: Lines<B>,
+    reader: B,
+    buf: Vec<u8>,
     line_no: usize,
     is_error: bool,
+    pending: Option<LineItem>,
+    partial: Partial,
+    schema: FieldSchema,
+    prefix: Option<PrefixExtractor>,
+    format: InputFormat,
+    delimiter: Option<RecordDelimiter>,
 }
 
 impl<B: BufRead> LogEntryIterator<B> {
     pub fn from_buf_reader(reader: B) -> Self {
+        Self::with_schema(reader, FieldSchema::default())
+    }
+
+    /// Creates a new `LogEntryIterator` that picks the core fields of each
+    /// entry according to the given schema.
+    pub fn with_schema(reader: B, schema: FieldSchema) -> Self {
         Self {
-            lines: reader.lines(),
+            reader,
+            buf: Vec::new(),
             line_no: 0,
             is_error: false,
+            pending: None,
+            partial: Partial::default(),
+            schema,
+            prefix: None,
+            format: InputFormat::Json,
+            delimiter: None,
+        }
+    }
+
+    /// Sets an extractor for lines that have a prefix in front of the json
+    /// object. The label of the prefix is kept in [`LogEntry::label`].
+    pub fn with_prefix(mut self, prefix: PrefixExtractor) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    /// Sets the format of the lines, json by default.
+    pub fn with_format(mut self, format: InputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the delimiter between records, instead of detecting it from the
+    /// start of the input.
+    pub fn with_delimiter(mut self, delimiter: RecordDelimiter) -> Self {
+        self.delimiter = Some(delimiter);
+        self
+    }
+
+    /// Parses a line, or adds it to the partial line and returns `None` if
+    /// it continues on the next one.
+    fn parse_line(&self, line: &str, partial: &mut Partial) -> Option<LineItem> {
+        let prefixed = self.prefix.as_ref().and_then(|p| p.extract(line));
+        let body = prefixed.as_ref().map_or(line, |p| p.json);
+        if prefixed.as_ref().is_some_and(|p| p.partial) {
+            partial.line.push_str(body);
+            return None;
+        }
+        if partial.line.is_empty() {
+            return self.parse_record(line, body, prefixed, partial);
+        }
+        let mut joined = std::mem::take(&mut partial.line);
+        joined.push_str(body);
+        self.parse_record(&joined, &joined, prefixed, partial)
+    }
+
+    /// Parses the body of a record, showing the whole text of the record if
+    /// it isn't an object.
+    fn parse_record(
+        &self,
+        text: &str,
+        body: &str,
+        prefixed: Option<PrefixedLine>,
+        partial: &mut Partial,
+    ) -> Option<LineItem> {
+        let mut map = match self.parse_object(body) {
+            Some(Ok(map)) => map,
+            Some(Err(e)) => return Some(LineItem::ParseError(self.line_no, e)),
+            None => return Some(LineItem::Text(self.line_no, text.to_string())),
+        };
+        let docker = take_docker_envelope(&mut map);
+        if let Some(docker) = &docker {
+            // the json-file driver splits long lines into records that don't
+            // end in a newline, interleaved with those of the other stream
+            if !docker.log.ends_with('\n') {
+                let start = partial.logs.entry(docker.stream.clone()).or_default();
+                start.push_str(&docker.log);
+                return None;
+            }
+            let joined;
+            let log = match partial.logs.remove(&docker.stream) {
+                Some(mut start) => {
+                    start.push_str(&docker.log);
+                    joined = start;
+                    &joined
+                }
+                None => &docker.log,
+            };
+            let log = log.trim_end_matches(['\n', '\r']);
+            map = match self.parse_object(log) {
+                Some(Ok(map)) => map,
+                Some(Err(e)) => return Some(LineItem::ParseError(self.line_no, e)),
+                None => return Some(LineItem::Text(self.line_no, log.to_string())),
+            };
+        }
+        let mut entry = match self.schema.entry_from_map(map) {
+            Ok(entry) => entry,
+            Err(e) => return Some(LineItem::ParseError(self.line_no, e)),
+        };
+        if let Some(docker) = docker {
+            if entry.timestamp.is_none() {
+                entry.timestamp = Some(Timestamp::parse(docker.time));
+            }
+            entry.extras.insert("stream".into(), docker.stream.into());
+            if let Some(attrs) = docker.attrs {
+                entry.extras.insert("attrs".into(), attrs);
+            }
         }
+        if let Some(prefixed) = prefixed {
+            if let Some(label) = prefixed.label {
+                entry.label = Some(label.into());
+            }
+            if entry.timestamp.is_none() {
+                entry.timestamp = prefixed.time.map(Timestamp::parse);
+            }
+        }
+        Some(LineItem::Entry(entry))
+    }
+
+    /// Parses the object on a line in the input format, or returns `None` if
+    /// the line is plain text.
+    fn parse_object(&self, text: &str) -> Option<Result<Map<String, Value>, serde_json::Error>> {
+        let is_json = text.trim_start().starts_with('{');
+        match self.format {
+            InputFormat::Json | InputFormat::Auto if is_json => Some(serde_json::from_str(text)),
+            InputFormat::Json => None,
+            InputFormat::Logfmt | InputFormat::Auto => logfmt::parse(text),
+        }
+    }
+}
+
+/// The start of a line split over several records, kept until the record
+/// that ends it.
+#[derive(Default)]
+struct Partial {
+    /// The bodies of partial lines from the prefix
+    line: String,
+    /// The `log` values of Docker records without a newline at the end, by
+    /// stream
+    logs: BTreeMap<String, String>,
+}
+
+impl Partial {
+    /// Takes out one of the lines left unfinished at the end of the input.
+    fn take_any(&mut self) -> Option<String> {
+        if !self.line.is_empty() {
+            return Some(std::mem::take(&mut self.line));
+        }
+        self.logs.pop_first().map(|(_, log)| log)
     }
 }
 
+/// The envelope the Docker json-file logging driver wraps each line in, like
+/// `{"log":"...\n","stream":"stdout","time":"..."}`.
+struct DockerEnvelope {
+    log: String,
+    stream: String,
+    time: String,
+    attrs: Option<Value>,
+}
+
+/// Takes the envelope fields out of the map if it is a Docker json-file log
+/// line, with a string `log`, a `stream` that is `stdout` or `stderr` and a
+/// string `time`.
+fn take_docker_envelope(map: &mut Map<String, Value>) -> Option<DockerEnvelope> {
+    let is_envelope = map.len() <= 4
+        && matches!(map.get("log"), Some(Value::String(_)))
+        && matches!(map.get("stream"), Some(Value::String(s)) if s == "stdout" || s == "stderr")
+        && matches!(map.get("time"), Some(Value::String(_)))
+        && map
+            .keys()
+            .all(|k| matches!(k.as_str(), "log" | "stream" | "time" | "attrs"));
+    if !is_envelope {
+        return None;
+    }
+    let (Some(Value::String(log)), Some(Value::String(stream)), Some(Value::String(time))) =
+        (map.remove("log"), map.remove("stream"), map.remove("time"))
+    else {
+        return None;
+    };
+    Some(DockerEnvelope {
+        log,
+        stream,
+        time,
+        attrs: map.remove("attrs"),
+    })
+}
+
 impl<B> Iterator for LogEntryIterator<B>
 where
     B: BufRead,
@@ -45,37 +344,69 @@ where
     type Item = LineItem;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.pending.take() {
+            return Some(item);
+        }
         if self.is_error {
             // If we previously encountered an error, we skip further
             // processing.
             return None;
         }
         use LineItem as L;
-        match self.lines.next() {
-            Some(Ok(line)) => {
-                self.line_no += 1;
-                Some(if line.is_empty() {
-                    L::EmptyLine(self.line_no)
-                } else {
-                    match serde_json::from_str::<LogEntry>(&line) {
-                        Ok(entry) => L::Entry(entry),
-                        Err(e) => L::ParseError(self.line_no, e),
+        let delimiter = *self
+            .delimiter
+            .get_or_insert_with(|| RecordDelimiter::detect(&mut self.reader));
+        loop {
+            self.buf.clear();
+            return match self.reader.read_until(delimiter.byte(), &mut self.buf) {
+                Ok(0) => {
+                    // lines cut off at the end are shown as they are
+                    let rest = self.partial.take_any()?;
+                    self.parse_record(&rest, &rest, None, &mut Partial::default())
+                }
+                Ok(_) => {
+                    let record = delimiter.trim(&self.buf);
+                    if record.is_empty() && delimiter != RecordDelimiter::Newline {
+                        continue;
                     }
-                })
-            }
-            Some(Err(e)) => {
-                self.line_no += 1;
-                // we set the error flag to true so that we don't continue
-                // processing further lines after an error. This prevents
-                // infinite looping in case of a persistent read error.
-                self.is_error = true;
-                Some(L::ReadError(self.line_no, e))
-            }
-            None => None,
+                    self.line_no += 1;
+                    let line = String::from_utf8_lossy(record);
+                    let item = if line.is_empty() {
+                        Some(L::EmptyLine(self.line_no))
+                    } else {
+                        let mut partial = std::mem::take(&mut self.partial);
+                        let item = self.parse_line(&line, &mut partial);
+                        self.partial = partial;
+                        item
+                    };
+                    match (line, item) {
+                        (Cow::Owned(_), item) => {
+                            self.pending = item;
+                            Some(L::EncodingWarning(self.line_no))
+                        }
+                        (Cow::Borrowed(_), Some(item)) => Some(item),
+                        (Cow::Borrowed(_), None) => continue,
+                    }
+                }
+                Err(e) => {
+                    self.line_no += 1;
+                    // we set the error flag to true so that we don't continue
+                    // processing further lines after an error. This prevents
+                    // infinite looping in case of a persistent read error.
+                    self.is_error = true;
+                    Some(L::ReadError(self.line_no, e))
+                }
+            };
         }
     }
 }
 
+/// Removes a trailing `\n` or `\r\n` from a line.
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::BufReader;
@@ -87,6 +418,7 @@ mod tests {
         let raw = r#"{"timestamp": "2024-03-15T12:34:56.123Z", "level": "info", "message": "This is a log message"}
 
 {bad}
+plain text
 {"timestamp": "2024-03-15T12:34:56.123Z", "level": "info", "message": "This is a log message"}
 
 {"timestamp": "2024-03-15T12:34:56.123Z", "level": "info", "message": "This is a log message"}"#
@@ -94,13 +426,244 @@ mod tests {
 
         let entries =
             LogEntryIterator::from_buf_reader(BufReader::new(raw.as_bytes())).collect::<Vec<_>>();
-        assert_eq!(entries.len(), 6);
+        assert_eq!(entries.len(), 7);
         assert!(matches!(entries[0], LineItem::Entry(_)));
         assert!(matches!(entries[1], LineItem::EmptyLine(2)));
         assert!(matches!(entries[2], LineItem::ParseError(3, _)));
-        assert!(matches!(entries[3], LineItem::Entry(_)));
-        assert!(matches!(entries[4], LineItem::EmptyLine(5)));
-        assert!(matches!(entries[5], LineItem::Entry(_)));
+        assert!(matches!(&entries[3], LineItem::Text(4, text) if text == "plain text"));
+        assert!(matches!(entries[4], LineItem::Entry(_)));
+        assert!(matches!(entries[5], LineItem::EmptyLine(6)));
+        assert!(matches!(entries[6], LineItem::Entry(_)));
+    }
+
+    #[test]
+    fn iterator_uses_schema() {
+        let raw = r#"{"when": "2024-03-15T12:34:56.123Z", "level": "info", "message": "Hi"}
+{"timestamp": "2024-03-15T12:34:56.123Z", "level": "info", "message": "Hi"}"#;
+
+        let mut schema = FieldSchema {
+            strict: true,
+            ..Default::default()
+        };
+        schema.set_candidates(crate::CoreField::Timestamp, ["when"]);
+        let entries = LogEntryIterator::with_schema(BufReader::new(raw.as_bytes()), schema)
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(entries[0], LineItem::Entry(_)));
+        assert!(matches!(entries[1], LineItem::ParseError(2, _)));
+    }
+
+    #[test]
+    fn iterator_recovers_from_invalid_utf8() {
+        let mut raw = Vec::new();
+        raw.extend_from_slice(
+            b"{\"timestamp\": \"t\", \"level\": \"info\", \"message\": \"bad \xff\xfe\"}\r\n",
+        );
+        raw.extend_from_slice(b"\xc3\n");
+        raw.extend_from_slice(
+            b"{\"timestamp\": \"t\", \"level\": \"info\", \"message\": \"good\"}\r\n",
+        );
+
+        let entries =
+            LogEntryIterator::from_buf_reader(BufReader::new(raw.as_slice())).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 5);
+        assert!(matches!(entries[0], LineItem::EncodingWarning(1)));
+        match &entries[1] {
+            LineItem::Entry(entry) => assert_eq!(entry.message, "bad \u{fffd}\u{fffd}"),
+            _ => panic!("expected an entry"),
+        }
+        assert!(matches!(entries[2], LineItem::EncodingWarning(2)));
+        assert!(matches!(entries[3], LineItem::Text(2, _)));
+        assert!(matches!(entries[4], LineItem::Entry(_)));
+    }
+
+    #[test]
+    fn iterator_extracts_prefixes() {
+        let raw = r#"2024-03-15T12:34:56.123Z stdout F {"level": "info", "message": "Hi"}
+2024-03-15T12:34:56.123Z stderr F {"time": "2024-03-15T12:34:57Z", "level": "info", "message": "Hi"}
+2024-03-15T12:34:56.123Z stderr F panicked
+{"time": "2024-03-15T12:34:57Z", "level": "info", "message": "Hi"}"#;
+
+        let entries = LogEntryIterator::from_buf_reader(BufReader::new(raw.as_bytes()))
+            .with_prefix(PrefixExtractor::cri())
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 4);
+        let LineItem::Entry(entry) = &entries[0] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.label.as_deref(), Some("stdout"));
+        assert_eq!(entry.timestamp_short(), "12:34:56.123");
+        let LineItem::Entry(entry) = &entries[1] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.label.as_deref(), Some("stderr"));
+        assert_eq!(entry.timestamp_short(), "12:34:57.000");
+        assert!(matches!(entries[2], LineItem::Text(3, _)));
+        let LineItem::Entry(entry) = &entries[3] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.label, None);
+    }
+
+    #[test]
+    fn iterator_joins_partial_cri_lines() {
+        let raw = r#"2024-03-15T12:34:56.123Z stdout P {"level": "info",
+2024-03-15T12:34:56.123Z stdout P  "message":
+2024-03-15T12:34:56.123Z stdout F  "Hi"}
+2024-03-15T12:34:57.123Z stdout F {"level": "info", "message": "next"}
+2024-03-15T12:34:58.123Z stdout P {"message": "cut"#;
+
+        let entries = LogEntryIterator::from_buf_reader(BufReader::new(raw.as_bytes()))
+            .with_prefix(PrefixExtractor::cri())
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 3);
+        let LineItem::Entry(entry) = &entries[0] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.message, "Hi");
+        assert_eq!(entry.label.as_deref(), Some("stdout"));
+        assert!(matches!(&entries[1], LineItem::Entry(entry) if entry.message == "next"));
+        assert!(matches!(entries[2], LineItem::ParseError(5, _)));
+    }
+
+    #[test]
+    fn iterator_unwraps_docker_envelopes() {
+        let raw = r#"{"log":"{\"level\":\"info\",\"msg\":\"Hi\"}\n","stream":"stdout","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"{\"time\":\"2024-03-15T12:34:57Z\",\"level\":\"info\",\"msg\":\"Hi\"}\n","stream":"stderr","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"panicked at main.rs\n","stream":"stderr","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"{bad\n","stream":"stderr","time":"2024-03-15T12:34:56.123456789Z"}"#;
+
+        let entries =
+            LogEntryIterator::from_buf_reader(BufReader::new(raw.as_bytes())).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 4);
+        let LineItem::Entry(entry) = &entries[0] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.message, "Hi");
+        assert_eq!(entry.timestamp_short(), "12:34:56.123");
+        assert_eq!(entry.extras.get("stream"), Some(&Value::from("stdout")));
+        assert_eq!(entry.extras.len(), 1);
+        let LineItem::Entry(entry) = &entries[1] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.timestamp_short(), "12:34:57.000");
+        assert!(matches!(&entries[2], LineItem::Text(3, text) if text == "panicked at main.rs"));
+        assert!(matches!(entries[3], LineItem::ParseError(4, _)));
+    }
+
+    #[test]
+    fn iterator_joins_split_docker_records() {
+        let raw = r#"{"log":"{\"level\":\"info\",","stream":"stdout","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"\"msg\":","stream":"stdout","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"\"Hi\"}\n","stream":"stdout","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"{\"msg\":\"next\"}\n","stream":"stdout","time":"2024-03-15T12:34:57.123456789Z"}"#;
+
+        let entries =
+            LogEntryIterator::from_buf_reader(BufReader::new(raw.as_bytes())).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        let LineItem::Entry(entry) = &entries[0] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.message, "Hi");
+        assert_eq!(entry.extras.get("stream"), Some(&Value::from("stdout")));
+        assert!(matches!(&entries[1], LineItem::Entry(entry) if entry.message == "next"));
+    }
+
+    #[test]
+    fn iterator_joins_split_docker_records_by_stream() {
+        let raw = r#"{"log":"{\"msg\":\"out","stream":"stdout","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"{\"msg\":\"err","stream":"stderr","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"put\"}\n","stream":"stdout","time":"2024-03-15T12:34:56.123456789Z"}
+{"log":"or\"}\n","stream":"stderr","time":"2024-03-15T12:34:56.123456789Z"}"#;
+
+        let entries =
+            LogEntryIterator::from_buf_reader(BufReader::new(raw.as_bytes())).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        let LineItem::Entry(entry) = &entries[0] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.message, "output");
+        assert_eq!(entry.extras.get("stream"), Some(&Value::from("stdout")));
+        let LineItem::Entry(entry) = &entries[1] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.message, "error");
+        assert_eq!(entry.extras.get("stream"), Some(&Value::from("stderr")));
+    }
+
+    #[test]
+    fn iterator_keeps_objects_that_only_look_like_docker_envelopes() {
+        let raw = r#"{"log":"audit","stream":"events","time":"2024-03-15T12:34:56Z","message":"Hi"}
+{"log":"audit","stream":"events","time":"2024-03-15T12:34:56Z"}"#;
+
+        let entries =
+            LogEntryIterator::from_buf_reader(BufReader::new(raw.as_bytes())).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(&entries[0], LineItem::Entry(entry) if entry.message == "Hi"));
+        let LineItem::Entry(entry) = &entries[1] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.extras.get("log"), Some(&Value::from("audit")));
+        assert_eq!(entry.extras.get("stream"), Some(&Value::from("events")));
+    }
+
+    #[test]
+    fn iterator_reads_logfmt_and_detects_formats() {
+        let raw = r#"ts=2024-03-15T12:34:56.123Z level=warn msg="Slow request" user=42
+{"timestamp": "2024-03-15T12:34:57Z", "level": "info", "message": "Hi"}
+panicked at main.rs
+level=info msg="unterminated"#;
+
+        let parse = |format| {
+            LogEntryIterator::from_buf_reader(BufReader::new(raw.as_bytes()))
+                .with_format(format)
+                .collect::<Vec<_>>()
+        };
+        let entries = parse(InputFormat::Auto);
+        assert_eq!(entries.len(), 4);
+        let LineItem::Entry(entry) = &entries[0] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.timestamp_short(), "12:34:56.123");
+        assert_eq!(entry.level(), crate::SeverityLevel::Warn);
+        assert_eq!(entry.message, "Slow request");
+        assert_eq!(entry.extras.get("user"), Some(&Value::from(42)));
+        assert!(matches!(entries[1], LineItem::Entry(_)));
+        assert!(matches!(entries[2], LineItem::Text(3, _)));
+        assert!(matches!(entries[3], LineItem::ParseError(4, _)));
+
+        let entries = parse(InputFormat::Logfmt);
+        assert!(matches!(entries[0], LineItem::Entry(_)));
+        assert!(matches!(entries[1], LineItem::Text(2, _)));
+        let entries = parse(InputFormat::Json);
+        assert!(matches!(entries[0], LineItem::Text(1, _)));
+        assert!(matches!(entries[1], LineItem::Entry(_)));
+    }
+
+    #[test]
+    fn iterator_splits_records_on_delimiters() {
+        let raw = b"\x1e{\n  \"level\": \"info\",\n  \"message\": \"multi\\nline\"\n}\n\x1e{\"message\": \"second\"}\n";
+        let entries =
+            LogEntryIterator::from_buf_reader(BufReader::new(&raw[..])).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(&entries[0], LineItem::Entry(entry) if entry.message == "multi\nline"));
+        assert!(matches!(&entries[1], LineItem::Entry(entry) if entry.message == "second"));
+
+        let raw = b"{\"message\": \"first\"}\0\0{bad}\0plain\ntext\0";
+        let entries = LogEntryIterator::from_buf_reader(BufReader::new(&raw[..]))
+            .with_delimiter(RecordDelimiter::Nul)
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(entries[0], LineItem::Entry(_)));
+        assert!(matches!(entries[1], LineItem::ParseError(2, _)));
+        assert!(matches!(&entries[2], LineItem::Text(3, text) if text == "plain\ntext"));
+
+        // without detection, the record separators are part of the lines
+        let raw = b"\x1e{\"message\": \"first\"}\n";
+        let entries = LogEntryIterator::from_buf_reader(BufReader::new(&raw[..]))
+            .with_delimiter(RecordDelimiter::Newline)
+            .collect::<Vec<_>>();
+        assert!(matches!(entries[0], LineItem::Text(1, _)));
     }
 
     struct ErrorReader;
@@ -1,14 +1,33 @@
 mod ansi;
 mod entry;
+mod filter;
 mod formatter;
+mod grep;
 mod iterator;
+mod logfmt;
+mod merge;
+mod prefix;
+mod preset;
 mod processor;
+mod query;
+mod schema;
+mod stream;
+mod timestamp;
 mod value_printer;
 
 // --------------------------------------------------------------------------
 
-pub use entry::{LogEntry, SeverityLevel};
-pub use formatter::LogEntryFormatter;
-pub use iterator::{LineItem, LogEntryIterator};
+pub use entry::{LogEntry, NumericLevels, SeverityLevel};
+pub use filter::{LevelRange, TimeBound, TimeRange};
+pub use formatter::{LogEntryFormatter, TimeDisplay};
+pub use grep::Grep;
+pub use iterator::{InputFormat, LineItem, LogEntryIterator, RecordDelimiter};
+pub use merge::LogEntryMerger;
+pub use prefix::{PrefixExtractor, PrefixedLine};
+pub use preset::Preset;
 pub use processor::{LogEntryProcessor, ProcessorOptions};
+pub use query::Query;
+pub use schema::{CoreField, FieldSchema};
+pub use stream::JsonStreamIterator;
+pub use timestamp::Timestamp;
 pub use value_printer::{ValuePrinter, ValuePrinterConfig};
@@ -0,0 +1,146 @@
+use serde::de::Error as _;
+use serde_json::{Map, Value};
+
+// --------------------------------------------------------------------------
+
+/// Parses a logfmt line like `ts=... level=info msg="Hi there" user=42` into
+/// a json object.
+///
+/// Returns `None` if the line doesn't look like logfmt, which is when it
+/// doesn't start with a `key=value` pair. Quoted values are kept as strings,
+/// unquoted values are read as numbers or booleans where possible, and keys
+/// without a value are `true`.
+pub(crate) fn parse(line: &str) -> Option<Result<Map<String, Value>, serde_json::Error>> {
+    let mut parser = Parser {
+        rest: line.trim_start(),
+    };
+    let (key, Some(value)) = parser.pair().ok()? else {
+        return None;
+    };
+    let mut map = Map::new();
+    map.insert(key, value);
+    loop {
+        parser.rest = parser.rest.trim_start();
+        if parser.rest.is_empty() {
+            return Some(Ok(map));
+        }
+        match parser.pair() {
+            Ok((key, value)) => {
+                map.insert(key, value.unwrap_or(Value::Bool(true)));
+            }
+            Err(e) => return Some(Err(serde_json::Error::custom(e))),
+        }
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    /// Reads a `key`, `key=value` or `key="value"` pair.
+    fn pair(&mut self) -> Result<(String, Option<Value>), String> {
+        let end = self
+            .rest
+            .find(|c: char| c == '=' || c == '"' || c.is_whitespace())
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err(format!("expected a key at `{}`", self.rest));
+        }
+        let key = self.rest[..end].to_string();
+        self.rest = &self.rest[end..];
+        let Some(rest) = self.rest.strip_prefix('=') else {
+            return Ok((key, None));
+        };
+        self.rest = rest;
+        let value = if self.rest.starts_with('"') {
+            Value::String(self.quoted()?)
+        } else {
+            let end = self
+                .rest
+                .find(char::is_whitespace)
+                .unwrap_or(self.rest.len());
+            let value = &self.rest[..end];
+            self.rest = &self.rest[end..];
+            unquoted_value(value)
+        };
+        Ok((key, Some(value)))
+    }
+
+    /// Reads a quoted string, handling backslash escapes.
+    fn quoted(&mut self) -> Result<String, String> {
+        let mut value = String::new();
+        let mut chars = self.rest.char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, c)) => value.push(c),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        Err("unterminated quoted value".to_string())
+    }
+}
+
+/// Reads an unquoted value as a number or boolean if it is one, and as a
+/// string otherwise.
+fn unquoted_value(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => serde_json::from_str::<serde_json::Number>(value)
+            .map(Value::Number)
+            .unwrap_or_else(|_| Value::String(value.to_string())),
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_logfmt_lines() {
+        let map = parse(
+            r#"ts=2024-03-15T12:34:56.123Z level=info msg="Hi \"there\"" user=42 ok=true cached"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(map["ts"], "2024-03-15T12:34:56.123Z");
+        assert_eq!(map["level"], "info");
+        assert_eq!(map["msg"], "Hi \"there\"");
+        assert_eq!(map["user"], 42);
+        assert_eq!(map["ok"], true);
+        assert_eq!(map["cached"], true);
+        assert_eq!(parse("empty= quoted=\"\"").unwrap().unwrap()["empty"], "");
+    }
+
+    #[test]
+    fn text_is_not_logfmt() {
+        for line in [
+            "",
+            "thread 'main' panicked",
+            "  boom",
+            "=value",
+            "\"quoted\"=1",
+        ] {
+            assert!(parse(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        assert!(parse(r#"level=info msg="unterminated"#).unwrap().is_err());
+        assert!(parse(r#"level=info "quoted""#).unwrap().is_err());
+    }
+}
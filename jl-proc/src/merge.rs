@@ -0,0 +1,208 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::LineItem;
+
+// --------------------------------------------------------------------------
+
+/// Merges several streams of log entries into one, ordered by timestamp.
+///
+/// Only the next item of each stream is held in memory, so streams of any
+/// size can be merged. Each item comes with the index of its stream.
+///
+/// Items without a parseable timestamp, like text lines, errors and entries
+/// with a malformed timestamp, are kept right after the previous item of
+/// their own stream, or at the start of the merge if their stream has no
+/// timestamps before them. Items with the same timestamp are ordered by the
+/// index of their stream.
+///
+/// The streams are expected to be sorted by timestamp themselves. Entries
+/// that are out of order within a stream are still shown in the order of
+/// their stream.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::{LineItem, LogEntry, LogEntryMerger};
+///
+/// let api = vec![
+///     LineItem::Entry(LogEntry::new("2024-03-15T12:00:01Z", "info", "a1")),
+///     LineItem::Entry(LogEntry::new("2024-03-15T12:00:03Z", "info", "a2")),
+/// ];
+/// let db = vec![LineItem::Entry(LogEntry::new("2024-03-15T12:00:02Z", "info", "d1"))];
+/// let messages: Vec<_> = LogEntryMerger::new(vec![api.into_iter(), db.into_iter()])
+///     .map(|(index, item)| match item {
+///         LineItem::Entry(entry) => format!("{index}:{}", entry.message),
+///         _ => unreachable!(),
+///     })
+///     .collect();
+/// assert_eq!(messages, ["0:a1", "1:d1", "0:a2"]);
+/// ```
+pub struct LogEntryMerger<I: Iterator<Item = LineItem>> {
+    streams: Vec<I>,
+    last_keys: Vec<Option<jiff::Timestamp>>,
+    labels: Vec<String>,
+    heads: BinaryHeap<Head>,
+}
+
+impl<I: Iterator<Item = LineItem>> LogEntryMerger<I> {
+    pub fn new(streams: Vec<I>) -> Self {
+        let mut merger = Self {
+            last_keys: vec![None; streams.len()],
+            streams,
+            labels: Vec::new(),
+            heads: BinaryHeap::new(),
+        };
+        for index in 0..merger.streams.len() {
+            merger.pull(index);
+        }
+        merger
+    }
+
+    /// Sets a label for each stream, which is shown in front of its entries.
+    /// An entry that already has a label gets the stream label in front of
+    /// it, like `api/web-1`.
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Reads the next item of a stream into the heads.
+    fn pull(&mut self, index: usize) {
+        let Some(item) = self.streams[index].next() else {
+            return;
+        };
+        let instant = match &item {
+            LineItem::Entry(entry) => entry.timestamp.as_ref().and_then(|t| t.instant()),
+            _ => None,
+        };
+        if instant.is_some() {
+            self.last_keys[index] = instant;
+        }
+        self.heads.push(Head {
+            key: self.last_keys[index],
+            index,
+            item,
+        });
+    }
+}
+
+impl<I: Iterator<Item = LineItem>> Iterator for LogEntryMerger<I> {
+    type Item = (usize, LineItem);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Head {
+            index, mut item, ..
+        } = self.heads.pop()?;
+        self.pull(index);
+        if let (LineItem::Entry(entry), Some(label)) = (&mut item, self.labels.get(index)) {
+            entry.add_label(label);
+        }
+        Some((index, item))
+    }
+}
+
+/// The next item of a stream, ordered so the heap pops the earliest first.
+struct Head {
+    key: Option<jiff::Timestamp>,
+    index: usize,
+    item: LineItem,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, as the binary heap pops the greatest item
+        (other.key, other.index).cmp(&(self.key, self.index))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogEntry;
+
+    fn entry(time: &str, message: &str) -> LineItem {
+        LineItem::Entry(LogEntry::new(time, "info", message))
+    }
+
+    fn merge(streams: Vec<Vec<LineItem>>) -> Vec<String> {
+        let streams = streams.into_iter().map(Vec::into_iter).collect();
+        LogEntryMerger::new(streams)
+            .with_labels(vec!["a".into(), "b".into()])
+            .map(|(index, item)| match item {
+                LineItem::Entry(entry) => {
+                    format!("{}:{}", entry.label.unwrap_or_default(), entry.message)
+                }
+                LineItem::Text(_, text) => format!("{index}:{text}"),
+                _ => format!("{index}:other"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_by_timestamp() {
+        let merged = merge(vec![
+            vec![
+                entry("2024-03-15T12:00:01Z", "a1"),
+                entry("2024-03-15T12:00:03Z", "a3"),
+                entry("2024-03-15T12:00:05Z", "a5"),
+            ],
+            vec![
+                entry("2024-03-15T13:00:02+01:00", "b2"),
+                entry("2024-03-15T12:00:04Z", "b4"),
+            ],
+        ]);
+        assert_eq!(merged, ["a:a1", "b:b2", "a:a3", "b:b4", "a:a5"]);
+    }
+
+    #[test]
+    fn items_without_timestamp_stay_after_their_predecessor() {
+        let merged = merge(vec![
+            vec![
+                LineItem::Text(1, "banner".into()),
+                entry("2024-03-15T12:00:01Z", "a1"),
+                LineItem::Text(3, "trace".into()),
+                entry("yesterday", "a?"),
+                entry("2024-03-15T12:00:04Z", "a4"),
+            ],
+            vec![
+                entry("2024-03-15T12:00:01Z", "b1"),
+                entry("2024-03-15T12:00:02Z", "b2"),
+                LineItem::EmptyLine(3),
+            ],
+        ]);
+        assert_eq!(
+            merged,
+            [
+                "0:banner", "a:a1", "0:trace", "a:a?", "b:b1", "b:b2", "1:other", "a:a4"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_own_labels() {
+        let mut entry = LogEntry::new("2024-03-15T12:00:01Z", "info", "Hi");
+        entry.label = Some("web-1".into());
+        let merged: Vec<_> = LogEntryMerger::new(vec![vec![LineItem::Entry(entry)].into_iter()])
+            .with_labels(vec!["api".into()])
+            .collect();
+        let (0, LineItem::Entry(entry)) = &merged[0] else {
+            panic!("expected an entry")
+        };
+        assert_eq!(entry.label.as_deref(), Some("api/web-1"));
+    }
+}
@@ -0,0 +1,217 @@
+use std::str::FromStr;
+
+use regex::Regex;
+
+// --------------------------------------------------------------------------
+
+/// The parts of a line that has a prefix in front of the json object.
+#[derive(Debug, PartialEq)]
+pub struct PrefixedLine<'a> {
+    /// A label from the prefix, like a container or service name
+    pub label: Option<&'a str>,
+    /// A timestamp from the prefix
+    pub time: Option<&'a str>,
+    /// The json object following the prefix
+    pub json: &'a str,
+    /// Whether the object continues on the next line
+    pub partial: bool,
+}
+
+/// Extracts the json object from lines where some text comes before it, like
+/// the output of `kubectl logs --prefix` or `docker compose logs`.
+///
+/// An extractor is a list of regular expressions, tried in order. The json
+/// object is the `json` capture group of the first matching expression, or
+/// the rest of the line after the match if there is no such group. The
+/// optional `label` and `time` groups are kept as a label for the entry and
+/// as a timestamp for entries without one, and if the optional `partial`
+/// group matches, the object continues on the next line.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::PrefixExtractor;
+///
+/// let extractor = PrefixExtractor::compose();
+/// let line = extractor.extract(r#"web-1  | {"level":"info"}"#).unwrap();
+/// assert_eq!(line.label, Some("web-1"));
+/// assert_eq!(line.json, r#"{"level":"info"}"#);
+///
+/// let extractor = PrefixExtractor::regex(r"^(?<label>\w+): ").unwrap();
+/// let line = extractor.extract(r#"api: {"level":"info"}"#).unwrap();
+/// assert_eq!(line.label, Some("api"));
+/// assert_eq!(line.json, r#"{"level":"info"}"#);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrefixExtractor {
+    patterns: Vec<Regex>,
+}
+
+impl PrefixExtractor {
+    /// Lines from `kubectl logs --prefix`, like `[pod/web-7d9/app] {...}`.
+    pub fn kubectl() -> Self {
+        Self::built_in(&[r"^\[(?<label>[^\]]+)\] (?<json>\{.*)$"])
+    }
+
+    /// Lines in the CRI log format, like
+    /// `2024-03-15T12:34:56.123456789Z stdout F {...}`. The stream is used as
+    /// label, and lines tagged `P` are the start of a long line that the
+    /// runtime split.
+    pub fn cri() -> Self {
+        Self::built_in(&[r"^(?<time>\S+) (?<label>stdout|stderr) (?:F|(?<partial>P)) (?<json>.*)$"])
+    }
+
+    /// Lines from `docker compose logs`, like `web-1  | {...}`.
+    pub fn compose() -> Self {
+        Self::built_in(&[r"^(?<label>\S+)\s+\| (?<json>\{.*)$"])
+    }
+
+    /// Syslog lines in RFC 3164 (`Mar 15 12:34:56 host app[42]: {...}`) or
+    /// RFC 5424 format. The app name is used as label.
+    pub fn syslog() -> Self {
+        Self::built_in(&[
+            r"^(?:<\d{1,3}>)?[A-Z][a-z]{2} [ \d]\d \d\d:\d\d:\d\d \S+ (?<label>[^\s:\[]+)(?:\[\d+\])?: ?(?<json>\{.*)$",
+            r"^<\d{1,3}>1 (?<time>\S+) \S+ (?<label>\S+) \S+ \S+ (?:-|\[.*?\]) (?:\x{FEFF})?(?<json>\{.*)$",
+        ])
+    }
+
+    /// A custom regular expression, see the type documentation for the
+    /// capture groups used.
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            patterns: vec![Regex::new(pattern)?],
+        })
+    }
+
+    fn built_in(patterns: &[&str]) -> Self {
+        Self {
+            patterns: patterns
+                .iter()
+                .map(|p| Regex::new(p).expect("built-in prefix patterns are valid"))
+                .collect(),
+        }
+    }
+
+    /// Splits a line into its prefix parts and json object, or returns
+    /// `None` if the line doesn't have the prefix.
+    pub fn extract<'a>(&self, line: &'a str) -> Option<PrefixedLine<'a>> {
+        self.patterns.iter().find_map(|pattern| {
+            let captures = pattern.captures(line)?;
+            let json = match captures.name("json") {
+                Some(json) => json.as_str(),
+                None => &line[captures.get(0)?.end()..],
+            };
+            Some(PrefixedLine {
+                label: captures.name("label").map(|m| m.as_str()),
+                time: captures.name("time").map(|m| m.as_str()),
+                json,
+                partial: captures.name("partial").is_some(),
+            })
+        })
+    }
+}
+
+impl FromStr for PrefixExtractor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kubectl" => Ok(Self::kubectl()),
+            "cri" => Ok(Self::cri()),
+            "compose" | "docker-compose" => Ok(Self::compose()),
+            "syslog" => Ok(Self::syslog()),
+            other => Err(format!(
+                "unknown prefix format '{other}', expected one of: kubectl, cri, compose, syslog"
+            )),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{"level":"info","msg":"Hi"}"#;
+
+    #[test]
+    fn can_extract_built_in_prefixes() {
+        let cases = [
+            (
+                "kubectl",
+                "[pod/web-7d9/app] ",
+                Some("pod/web-7d9/app"),
+                None,
+            ),
+            (
+                "cri",
+                "2024-03-15T12:34:56.123456789Z stderr F ",
+                Some("stderr"),
+                Some("2024-03-15T12:34:56.123456789Z"),
+            ),
+            ("compose", "web-1     | ", Some("web-1"), None),
+            (
+                "syslog",
+                "Mar  5 12:34:56 host app[42]: ",
+                Some("app"),
+                None,
+            ),
+            (
+                "syslog",
+                "<13>Mar 15 12:34:56 host app: ",
+                Some("app"),
+                None,
+            ),
+            (
+                "syslog",
+                "<165>1 2024-03-15T12:34:56.123Z host app 42 ID47 - ",
+                Some("app"),
+                Some("2024-03-15T12:34:56.123Z"),
+            ),
+        ];
+        for (format, prefix, label, time) in cases {
+            let extractor = format.parse::<PrefixExtractor>().unwrap();
+            let line = format!("{prefix}{JSON}");
+            assert_eq!(
+                extractor.extract(&line),
+                Some(PrefixedLine {
+                    label,
+                    time,
+                    json: JSON,
+                    partial: false,
+                }),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn lines_without_prefix_are_not_extracted() {
+        assert_eq!(PrefixExtractor::kubectl().extract(JSON), None);
+        assert_eq!(PrefixExtractor::compose().extract("web-1 | started"), None);
+    }
+
+    #[test]
+    fn can_extract_with_custom_regex() {
+        let extractor = PrefixExtractor::regex(r"^(?<label>\w+) >>> ").unwrap();
+        let line = format!("worker >>> {JSON}");
+        let parts = extractor.extract(&line).unwrap();
+        assert_eq!(parts.label, Some("worker"));
+        assert_eq!(parts.json, JSON);
+        assert!(PrefixExtractor::regex("(").is_err());
+    }
+
+    #[test]
+    fn cri_partial_lines_are_marked() {
+        let line = PrefixExtractor::cri()
+            .extract(r#"2024-03-15T12:34:56Z stdout P {"msg":"#)
+            .unwrap();
+        assert!(line.partial);
+        assert_eq!(line.json, r#"{"msg":"#);
+        let line = PrefixExtractor::cri()
+            .extract(r#"2024-03-15T12:34:56Z stdout F "Hi"}"#)
+            .unwrap();
+        assert!(!line.partial);
+        assert_eq!(line.json, r#""Hi"}"#);
+    }
+}
@@ -0,0 +1,269 @@
+use std::str::FromStr;
+
+use crate::{FieldSchema, NumericLevels, SeverityLevel};
+
+// --------------------------------------------------------------------------
+
+/// Field schemas for the json output of common structured loggers.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::*;
+///
+/// let schema = "pino".parse::<Preset>().unwrap().schema();
+/// let json = r#"{"level":30,"time":1710506096123,"pid":7,"hostname":"web","msg":"Hi"}"#;
+/// let entry = schema.entry_from_map(serde_json::from_str(json).unwrap()).unwrap();
+/// assert_eq!(entry.level(), SeverityLevel::Info);
+/// assert_eq!(entry.message, "Hi");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preset {
+    /// pino (Node.js)
+    Pino,
+    /// bunyan (Node.js)
+    Bunyan,
+    /// winston (Node.js) with the json and timestamp formats
+    Winston,
+    /// zap (Go) production encoder
+    Zap,
+    /// logrus (Go) JSON formatter
+    Logrus,
+    /// log/slog (Go) JSON handler
+    Slog,
+    /// structlog (Python) JSON renderer
+    Structlog,
+    /// Serilog compact log event format (CLEF)
+    Clef,
+    /// Elastic Common Schema (ECS) loggers
+    Ecs,
+    /// Google Cloud Logging structured logs
+    Gcp,
+    /// systemd journal export from `journalctl -o json`
+    Journald,
+}
+
+impl Preset {
+    /// All the presets.
+    pub const ALL: [Preset; 11] = [
+        Preset::Pino,
+        Preset::Bunyan,
+        Preset::Winston,
+        Preset::Zap,
+        Preset::Logrus,
+        Preset::Slog,
+        Preset::Structlog,
+        Preset::Clef,
+        Preset::Ecs,
+        Preset::Gcp,
+        Preset::Journald,
+    ];
+
+    /// Returns the name of the preset.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Pino => "pino",
+            Preset::Bunyan => "bunyan",
+            Preset::Winston => "winston",
+            Preset::Zap => "zap",
+            Preset::Logrus => "logrus",
+            Preset::Slog => "slog",
+            Preset::Structlog => "structlog",
+            Preset::Clef => "clef",
+            Preset::Ecs => "ecs",
+            Preset::Gcp => "gcp",
+            Preset::Journald => "journald",
+        }
+    }
+
+    /// Returns the field schema for the preset.
+    pub fn schema(&self) -> FieldSchema {
+        use SeverityLevel as S;
+        let mut schema = match self {
+            Preset::Pino | Preset::Bunyan => make_schema(&["time"], &["level"], &["msg"], &[]),
+            Preset::Winston => make_schema(&["timestamp"], &["level"], &["message"], &[]),
+            Preset::Zap => make_schema(
+                &["ts"],
+                &["level"],
+                &["msg"],
+                &[("dpanic", S::Error), ("panic", S::Fatal)],
+            ),
+            Preset::Logrus => make_schema(&["time"], &["level"], &["msg"], &[]),
+            Preset::Slog => make_schema(&["time"], &["level"], &["msg"], &[]),
+            Preset::Structlog => make_schema(
+                &["timestamp"],
+                &["level"],
+                &["event"],
+                &[("exception", S::Error)],
+            ),
+            Preset::Clef => make_schema(&["@t"], &["@l"], &["@m", "@mt"], &[]),
+            Preset::Ecs => make_schema(&["@timestamp"], &["log.level"], &["message"], &[]),
+            Preset::Gcp => make_schema(
+                &["timestamp", "time"],
+                &["severity"],
+                &["message", "textPayload"],
+                &[("DEFAULT", S::Info)],
+            ),
+            Preset::Journald => {
+                make_schema(&["__REALTIME_TIMESTAMP"], &["PRIORITY"], &["MESSAGE"], &[])
+            }
+        };
+        if let Preset::Pino | Preset::Bunyan = self {
+            schema.numeric_levels = NumericLevels::Bunyan;
+        }
+        schema.default_level = match self {
+            // CLEF leaves out the level for 'Information' events
+            Preset::Clef => Some("Information".into()),
+            // GCP treats a missing severity as 'DEFAULT'
+            Preset::Gcp => Some("DEFAULT".into()),
+            _ => None,
+        };
+        if let Preset::Journald = self {
+            schema.numeric_levels = NumericLevels::Syslog;
+            schema.label = vec!["_SYSTEMD_UNIT".into(), "SYSLOG_IDENTIFIER".into()];
+            // the fields starting with '_' are added by journald itself
+            schema.hidden_prefixes = vec!["_".into()];
+            schema.message_schema = Some(Box::default());
+        }
+        schema
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Preset::ALL
+            .into_iter()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Preset::ALL.iter().map(|p| p.name()).collect();
+                format!(
+                    "unknown preset '{s}', expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+fn make_schema(
+    timestamp: &[&str],
+    level: &[&str],
+    message: &[&str],
+    levels: &[(&str, SeverityLevel)],
+) -> FieldSchema {
+    let to_strings = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect();
+    FieldSchema {
+        timestamp: to_strings(timestamp),
+        level: to_strings(level),
+        message: to_strings(message),
+        levels: levels
+            .iter()
+            .map(|(name, severity)| (name.to_string(), severity.clone()))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogEntry;
+
+    fn parse(preset: Preset, json: &str) -> LogEntry {
+        preset
+            .schema()
+            .entry_from_map(serde_json::from_str(json).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn preset_names_round_trip() {
+        for preset in Preset::ALL {
+            assert_eq!(preset.name().parse::<Preset>(), Ok(preset));
+        }
+        assert!("log4j".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn can_parse_preset_samples() {
+        let samples = [
+            (
+                Preset::Bunyan,
+                r#"{"name":"app","hostname":"h","pid":1,"level":50,"msg":"Boom","time":"2024-03-15T12:34:56.123Z","v":0}"#,
+                SeverityLevel::Error,
+            ),
+            (
+                Preset::Zap,
+                r#"{"level":"dpanic","ts":1710506096.123,"caller":"main.go:12","msg":"Boom"}"#,
+                SeverityLevel::Error,
+            ),
+            (
+                Preset::Slog,
+                r#"{"time":"2024-03-15T12:34:56.123+01:00","level":"WARN","msg":"Boom","n":1}"#,
+                SeverityLevel::Warn,
+            ),
+            (
+                Preset::Structlog,
+                r#"{"event":"Boom","level":"critical","timestamp":"2024-03-15T12:34:56.123Z"}"#,
+                SeverityLevel::Fatal,
+            ),
+            (
+                Preset::Clef,
+                r#"{"@t":"2024-03-15T12:34:56.123Z","@mt":"Boom {N}","N":1}"#,
+                SeverityLevel::Info,
+            ),
+            (
+                Preset::Ecs,
+                r#"{"@timestamp":"2024-03-15T12:34:56.123Z","log.level":"warn","message":"Boom","ecs.version":"1.6.0"}"#,
+                SeverityLevel::Warn,
+            ),
+            (
+                Preset::Gcp,
+                r#"{"severity":"CRITICAL","message":"Boom","time":"2024-03-15T12:34:56.123Z"}"#,
+                SeverityLevel::Fatal,
+            ),
+        ];
+        for (preset, json, severity) in samples {
+            let entry = parse(preset, json);
+            assert_eq!(entry.level(), severity, "{}", preset.name());
+            assert!(entry.message.starts_with("Boom"), "{}", preset.name());
+        }
+    }
+
+    #[test]
+    fn journald_hides_trusted_fields() {
+        let entry = parse(
+            Preset::Journald,
+            r#"{"__CURSOR":"s=1","__REALTIME_TIMESTAMP":"1710506096123456","PRIORITY":"4","_PID":"42","_SYSTEMD_UNIT":"web.service","SYSLOG_IDENTIFIER":"web","MESSAGE":"Slow request","CODE_LINE":"12"}"#,
+        );
+        assert_eq!(entry.timestamp_short(), "12:34:56.123");
+        assert_eq!(entry.level(), SeverityLevel::Warn);
+        assert_eq!(entry.message, "Slow request");
+        assert_eq!(entry.label.as_deref(), Some("web.service"));
+        assert_eq!(entry.extras.len(), 2);
+        assert!(entry.extras.contains_key("CODE_LINE"));
+        assert!(entry.extras.contains_key("SYSLOG_IDENTIFIER"));
+    }
+
+    #[test]
+    fn journald_reads_json_messages() {
+        let entry = parse(
+            Preset::Journald,
+            r#"{"__REALTIME_TIMESTAMP":"1710506096123456","PRIORITY":"6","SYSLOG_IDENTIFIER":"api","MESSAGE":"{\"level\":\"error\",\"msg\":\"Boom\",\"user\":7}"}"#,
+        );
+        assert_eq!(entry.level(), SeverityLevel::Error);
+        assert_eq!(entry.message, "Boom");
+        assert_eq!(entry.timestamp_short(), "12:34:56.123");
+        assert_eq!(entry.label.as_deref(), Some("api"));
+        assert_eq!(entry.extras.get("user"), Some(&7.into()));
+
+        let entry = parse(
+            Preset::Journald,
+            r#"{"PRIORITY":"3","MESSAGE":"{not json"}"#,
+        );
+        assert_eq!(entry.level(), SeverityLevel::Error);
+        assert_eq!(entry.message, "{not json");
+    }
+}
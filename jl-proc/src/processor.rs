@@ -1,14 +1,32 @@
-use std::io::Write;
+use std::{collections::HashMap, io::Write};
 
-use crate::{LineItem, LogEntryFormatter};
+use crate::{
+    Grep, LevelRange, LineItem, LogEntryFormatter, Query, TimeRange, filter::ItemFilter,
+    grep::GrepContext,
+};
 
 // --------------------------------------------------------------------------
 
 /// The options for processing log entries.
+#[derive(Default)]
 pub struct ProcessorOptions {
     /// if true, skip empty lines in the input
     pub skip_empty_lines: bool,
     pub session_start: Option<String>,
+    /// if true, show a header with the source whenever it changes
+    pub show_sources: bool,
+    /// if true, flush the output after each item, for following inputs that
+    /// are still being written
+    pub flush: bool,
+    /// only show the entries in this time range
+    pub time_range: TimeRange,
+    /// if set, only show the entries with a level in this range, and those
+    /// without a known level if it goes down to verbose
+    pub levels: Option<LevelRange>,
+    /// if set, only show the entries matching this query
+    pub query: Option<Query>,
+    /// if set, only show the entries the search matches, with their context
+    pub grep: Option<Grep>,
 }
 
 // --------------------------------------------------------------------------
@@ -29,35 +47,94 @@ impl LogEntryProcessor {
         entries: impl Iterator<Item = LineItem>,
         source: &str,
         fmt: &mut LogEntryFormatter<W>,
+    ) -> std::io::Result<()> {
+        self.process(entries.map(|item| (source, item)), fmt)
+    }
+
+    /// Processes the items of several sources, like the output of a
+    /// [`LogEntryMerger`](crate::LogEntryMerger), where each item comes with
+    /// the index of its source in `sources`.
+    pub fn process_merged<W: Write>(
+        &self,
+        items: impl Iterator<Item = (usize, LineItem)>,
+        sources: &[&str],
+        fmt: &mut LogEntryFormatter<W>,
+    ) -> std::io::Result<()> {
+        self.process(items.map(|(index, item)| (sources[index], item)), fmt)
+    }
+
+    fn process<'s, W: Write>(
+        &self,
+        items: impl Iterator<Item = (&'s str, LineItem)>,
+        fmt: &mut LogEntryFormatter<W>,
     ) -> std::io::Result<()> {
         let mut continuous_empty_lines = 0;
+        let mut empty_lines_source = "";
+        // merged sources each keep track of their own previous entry
+        let mut filters = HashMap::new();
 
-        for entry in entries {
-            match entry {
-                LineItem::Entry(log_entry) => {
-                    if continuous_empty_lines > 1 {
-                        if !self.options.skip_empty_lines {
-                            fmt.format_empty_lines(continuous_empty_lines, source)?;
-                        }
-                        continuous_empty_lines = 0;
-                    }
-                    if let Some(session_start) = &self.options.session_start
-                        && log_entry.message.starts_with(session_start)
-                    {
-                        fmt.format_session_start(&log_entry)?;
-                    }
-                    fmt.format_entry(&log_entry)?;
+        let mut context = self.options.grep.clone().map(GrepContext::new);
+        let mut shown = Vec::new();
+
+        for (source, item) in items {
+            let filter = filters
+                .entry(source)
+                .or_insert_with(|| ItemFilter::new(&self.options));
+            for item in filter.take(item).into_iter().flatten() {
+                match &mut context {
+                    Some(context) => context.push(source, item, &mut shown),
+                    None => shown.push(Some((source, item))),
                 }
-                LineItem::EmptyLine(_) => {
-                    continuous_empty_lines += 1;
+            }
+            for grepped in shown.drain(..) {
+                let Some((source, entry)) = grepped else {
+                    fmt.format_context_separator()?;
+                    continue;
+                };
+                if self.options.show_sources && !matches!(entry, LineItem::EmptyLine(_)) {
+                    fmt.format_source_header(source)?;
                 }
-                LineItem::ReadError(line_no, e) => {
-                    fmt.format_read_error(line_no, source, e)?;
+                if continuous_empty_lines > 1
+                    && matches!(entry, LineItem::Entry(_) | LineItem::Text(..))
+                {
+                    if !self.options.skip_empty_lines {
+                        fmt.format_empty_lines(continuous_empty_lines, empty_lines_source)?;
+                    }
+                    continuous_empty_lines = 0;
                 }
-                LineItem::ParseError(line_no, e) => {
-                    fmt.format_parse_error(line_no, source, e)?;
+                match entry {
+                    LineItem::Entry(log_entry) => {
+                        if let Some(session_start) = &self.options.session_start
+                            && log_entry.message.starts_with(session_start)
+                        {
+                            fmt.format_session_start(&log_entry)?;
+                        }
+                        fmt.format_entry(&log_entry)?;
+                    }
+                    LineItem::Text(_, text) => {
+                        fmt.format_text(&text)?;
+                    }
+                    LineItem::EmptyLine(_) => {
+                        continuous_empty_lines += 1;
+                        empty_lines_source = source;
+                    }
+                    LineItem::EncodingWarning(line_no) => {
+                        fmt.format_encoding_warning(line_no, source)?;
+                    }
+                    LineItem::ReadError(line_no, e) => {
+                        fmt.format_read_error(line_no, source, e)?;
+                    }
+                    LineItem::ParseError(line_no, e) => {
+                        fmt.format_parse_error(line_no, source, e)?;
+                    }
+                    LineItem::Notice(notice) => {
+                        fmt.format_notice(source, &notice)?;
+                    }
                 }
             }
+            if self.options.flush {
+                fmt.flush()?;
+            }
         }
         Ok(())
     }
@@ -67,40 +144,157 @@ impl LogEntryProcessor {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
     use crate::LogEntry;
 
     use super::*;
 
+    /// Processes the items as the entries of `test.log`, and returns the
+    /// output without colors.
+    fn run(options: ProcessorOptions, items: Vec<LineItem>) -> String {
+        let processor = LogEntryProcessor::new(options);
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output);
+        let result = processor.process_entries(items.into_iter(), "test.log", &mut formatter);
+        assert!(result.is_ok());
+        String::from_utf8(output).unwrap()
+    }
+
     #[test]
     fn shows_sources_when_they_change() {
         let options = ProcessorOptions {
-            skip_empty_lines: false,
-            session_start: None,
+            show_sources: true,
+            ..Default::default()
         };
-        let entries = vec![
-            LineItem::Entry(LogEntry {
-                timestamp: "2024-01-01T10:32:51.123Z".into(),
-                level: "info".into(),
-                message: "A log message".into(),
-                extras: HashMap::default(),
-            }),
-            LineItem::Entry(LogEntry {
-                timestamp: "2024-01-01T10:32:53.456Z".into(),
-                level: "warn".into(),
-                message: "Another log message".into(),
-                extras: HashMap::default(),
-            }),
-        ];
         let processor = LogEntryProcessor::new(options);
         let mut output = Vec::new();
         let mut formatter = LogEntryFormatter::new(false, &mut output);
+        let items =
+            [(0, "first"), (0, "second"), (1, "third"), (0, "fourth")].map(|(source, message)| {
+                let entry = LogEntry::new("2024-01-01T10:32:51.123Z", "info", message);
+                (source, LineItem::Entry(entry))
+            });
+        let result =
+            processor.process_merged(items.into_iter(), &["a.log", "b.log"], &mut formatter);
+        assert!(result.is_ok());
+        let expected = "==> a.log <==\n\
+10:32:51.123 [inf] first\n\
+10:32:51.123 [inf] second\n\
+\n\
+==> b.log <==\n\
+10:32:51.123 [inf] third\n\
+\n\
+==> a.log <==\n\
+10:32:51.123 [inf] fourth\n";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn shows_entries_without_core_fields() {
+        let mut entry = LogEntry::new("2024-01-01T10:32:51.123Z", "info", "");
+        entry.timestamp = None;
+        entry.extras.insert("status".into(), 200.into());
+        assert_eq!(
+            run(ProcessorOptions::default(), vec![LineItem::Entry(entry)]),
+            "             [inf] \n    status: 200\n"
+        );
+    }
+
+    #[test]
+    fn shows_text_lines_between_entries() {
+        let entries = vec![
+            LineItem::Entry(LogEntry::new(
+                "2024-01-01T10:32:51.123Z",
+                "info",
+                "Starting",
+            )),
+            LineItem::Text(2, "thread 'main' panicked at src/main.rs:2:5:".into()),
+            LineItem::Text(3, "  boom".into()),
+        ];
+        let processor = LogEntryProcessor::new(ProcessorOptions::default());
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output).with_text_marker("| ");
         let result = processor.process_entries(entries.into_iter(), "test.log", &mut formatter);
         assert!(result.is_ok());
         let output_str = String::from_utf8(output).unwrap();
-        let expected = "10:32:51.123 [inf] A log message\n\
-10:32:53.456 [wrn] Another log message\n";
+        let expected = "10:32:51.123 [inf] Starting\n\
+| thread 'main' panicked at src/main.rs:2:5:\n\
+|   boom\n";
         assert_eq!(output_str, expected);
     }
+
+    #[test]
+    fn shows_source_headers_when_enabled() {
+        let options = ProcessorOptions {
+            show_sources: true,
+            ..Default::default()
+        };
+        let processor = LogEntryProcessor::new(options);
+        let mut output = Vec::new();
+        let mut formatter = LogEntryFormatter::new(false, &mut output);
+        for (source, message) in [("a.log", "first"), ("a.log", "second"), ("b.log", "third")] {
+            let entries = vec![
+                LineItem::EmptyLine(1),
+                LineItem::Entry(LogEntry::new("2024-01-01T10:32:51.123Z", "info", message)),
+            ];
+            let result = processor.process_entries(entries.into_iter(), source, &mut formatter);
+            assert!(result.is_ok());
+        }
+        let output_str = String::from_utf8(output).unwrap();
+        let expected = "==> a.log <==\n\
+10:32:51.123 [inf] first\n\
+10:32:51.123 [inf] second\n\
+\n\
+==> b.log <==\n\
+10:32:51.123 [inf] third\n";
+        assert_eq!(output_str, expected);
+    }
+
+    #[test]
+    fn shows_only_entries_in_time_range() {
+        let options = ProcessorOptions {
+            time_range: TimeRange {
+                since: Some("2024-01-01T10:32:52Z".parse().unwrap()),
+                until: Some("2024-01-01T10:32:54Z".parse().unwrap()),
+            },
+            ..Default::default()
+        };
+        let entries = vec![
+            LineItem::Entry(LogEntry::new("2024-01-01T10:32:51.123Z", "info", "Early")),
+            LineItem::Text(2, "  early trace".into()),
+            LineItem::Entry(LogEntry::new("2024-01-01T10:32:53.456Z", "warn", "Inside")),
+            LineItem::Text(4, "  inside trace".into()),
+            LineItem::Entry(LogEntry::new("2024-01-01T10:32:55Z", "info", "Late")),
+        ];
+        assert_eq!(
+            run(options, entries),
+            "10:32:53.456 [wrn] Inside\n  inside trace\n"
+        );
+    }
+
+    #[test]
+    fn shows_separators_between_grep_matches() {
+        let options = ProcessorOptions {
+            grep: Some(Grep {
+                after: 1,
+                ..Grep::new(regex::Regex::new("fail").unwrap())
+            }),
+            ..Default::default()
+        };
+        let entries = [
+            "Starting",
+            "Request failed",
+            "Retrying",
+            "Idle",
+            "Idle",
+            "Retry failed",
+        ]
+        .into_iter()
+        .map(|message| LineItem::Entry(LogEntry::new("2024-01-01T10:32:51.123Z", "info", message)))
+        .collect();
+        let expected = "10:32:51.123 [inf] Request failed\n\
+10:32:51.123 [inf] Retrying\n\
+--\n\
+10:32:51.123 [inf] Retry failed\n";
+        assert_eq!(run(options, entries), expected);
+    }
 }
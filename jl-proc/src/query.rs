@@ -0,0 +1,461 @@
+use std::{borrow::Cow, cmp::Ordering, collections::HashMap, str::FromStr};
+
+use regex::Regex;
+use serde_json::{Number, Value};
+
+use crate::LogEntry;
+
+// --------------------------------------------------------------------------
+
+/// A condition on the fields of a log entry, like
+/// `status >= 500 && user_id == 42 && path =~ "^/api"`.
+///
+/// A field is a path like `req.headers.host`, looked up in the extras, with
+/// `timestamp`, `level`, `message` and `label` for the core fields. Keys that
+/// contain dots themselves, like `http.status`, are found too, and array
+/// items are picked by their index, like `tags.0`.
+///
+/// Conditions are:
+/// - `field`: the field exists and isn't null
+/// - `field == value`, `!=`, `<`, `<=`, `>`, `>=`: compares the field with a
+///   number, a quoted string, `true`, `false` or `null`. Unquoted words are
+///   strings too. Numbers are compared as numbers, also when the field is a
+///   string holding a number, exactly if both are integers, and strings are
+///   compared alphabetically.
+/// - `field =~ "regex"`, `!~`: the field matches the regular expression,
+///   with numbers and other values matched as their json text
+///
+/// Conditions are combined with `&&`, `||`, `!` and parentheses. Comparisons
+/// on a missing field are false.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::{LogEntry, Query};
+///
+/// let query: Query = r#"status >= 500 && path =~ "^/api""#.parse().unwrap();
+/// let mut entry = LogEntry::new("2024-03-15T12:00:00Z", "error", "Failed");
+/// entry.extras.insert("status".into(), 503.into());
+/// entry.extras.insert("path".into(), "/api/users".into());
+/// assert!(query.matches(&entry));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Query {
+    expr: Expr,
+}
+
+impl Query {
+    /// Returns true if the entry meets the condition.
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.expr.eval(entry)
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { rest: s };
+        let expr = parser.or()?;
+        if !parser.rest.trim_start().is_empty() {
+            return Err(parser.error("`&&` or `||`"));
+        }
+        Ok(Self { expr })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(Vec<String>),
+    Compare(Vec<String>, Op, Value),
+    /// A regex match, negated if the flag is set
+    Match(Vec<String>, Regex, bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Expr {
+    fn eval(&self, entry: &LogEntry) -> bool {
+        match self {
+            Expr::Or(a, b) => a.eval(entry) || b.eval(entry),
+            Expr::And(a, b) => a.eval(entry) && b.eval(entry),
+            Expr::Not(expr) => !expr.eval(entry),
+            Expr::Exists(path) => field(entry, path).is_some_and(|v| !v.is_null()),
+            Expr::Compare(path, op, value) => {
+                let Some(field) = field(entry, path) else {
+                    return false;
+                };
+                let ordering = compare(&field, value);
+                match op {
+                    Op::Eq => ordering == Some(Ordering::Equal),
+                    Op::Ne => ordering != Some(Ordering::Equal),
+                    Op::Lt => ordering == Some(Ordering::Less),
+                    Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    Op::Gt => ordering == Some(Ordering::Greater),
+                    Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                }
+            }
+            Expr::Match(path, regex, negated) => {
+                field(entry, path).is_some_and(|field| regex.is_match(&text(&field)) != *negated)
+            }
+        }
+    }
+}
+
+/// Looks up the value of a field of the entry.
+fn field<'a>(entry: &'a LogEntry, path: &[String]) -> Option<Cow<'a, Value>> {
+    if let [name] = path {
+        let core = match name.as_str() {
+            "timestamp" => entry.timestamp.as_ref().map(|t| t.as_str().to_string()),
+            "level" => entry.level.clone(),
+            "message" => Some(entry.message.clone()),
+            "label" => entry.label.clone(),
+            _ => None,
+        };
+        if let Some(core) = core {
+            return Some(Cow::Owned(Value::String(core)));
+        }
+    }
+    lookup(&entry.extras, path).map(Cow::Borrowed)
+}
+
+/// Looks up a path in an object, trying keys with dots in them before
+/// nested objects.
+fn lookup<'a>(map: &'a HashMap<String, Value>, path: &[String]) -> Option<&'a Value> {
+    (1..=path.len())
+        .rev()
+        .find_map(|i| descend(map.get(&path[..i].join("."))?, &path[i..]))
+}
+
+/// Looks up the rest of a path inside a value.
+fn descend<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    let Some(first) = path.first() else {
+        return Some(value);
+    };
+    match value {
+        Value::Object(map) => (1..=path.len())
+            .rev()
+            .find_map(|i| descend(map.get(&path[..i].join("."))?, &path[i..])),
+        Value::Array(items) => descend(items.get(first.parse::<usize>().ok()?)?, &path[1..]),
+        _ => None,
+    }
+}
+
+/// Compares a field with a value, or returns `None` if they can't be
+/// compared.
+fn compare(field: &Value, value: &Value) -> Option<Ordering> {
+    match (field, value) {
+        (_, Value::Number(n)) => compare_numbers(&number(field)?, n),
+        (Value::Object(_) | Value::Array(_) | Value::Null, Value::String(_)) => None,
+        (_, Value::String(s)) => Some(text(field).as_ref().cmp(s.as_str())),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// Reads a field as a number, if it is one or is a string holding one.
+fn number(field: &Value) -> Option<Number> {
+    match field {
+        Value::Number(n) => Some(n.clone()),
+        Value::String(s) => {
+            let s = s.trim();
+            s.parse().ok().or_else(|| Number::from_f64(s.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Compares two numbers, exactly if both are integers and as floats
+/// otherwise, as large ids don't fit in a float.
+fn compare_numbers(a: &Number, b: &Number) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        return Some(a.cmp(&b));
+    }
+    if let (Some(a), Some(b)) = (a.as_u64(), b.as_u64()) {
+        return Some(a.cmp(&b));
+    }
+    Some(a.as_f64()?.total_cmp(&b.as_f64()?))
+}
+
+/// Returns the text of a field, which is the string itself for strings and
+/// the json text otherwise.
+fn text(field: &Value) -> Cow<'_, str> {
+    match field {
+        Value::String(s) => Cow::Borrowed(s),
+        other => Cow::Owned(other.to_string()),
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    /// Reads conditions joined by `||`.
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    /// Reads conditions joined by `&&`.
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    /// Reads a condition, negated or in parentheses.
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.or()?;
+            if !self.eat(")") {
+                return Err(self.error("`)`"));
+            }
+            return Ok(expr);
+        }
+        self.condition()
+    }
+
+    /// Reads a field, optionally followed by a comparison.
+    fn condition(&mut self) -> Result<Expr, String> {
+        let path = self.path()?;
+        for (token, op) in [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ] {
+            if self.eat(token) {
+                return Ok(Expr::Compare(path, op, self.value()?));
+            }
+        }
+        for (token, negated) in [("=~", false), ("!~", true)] {
+            if self.eat(token) {
+                let Value::String(pattern) = self.value()? else {
+                    return Err(format!("expected a quoted regex after `{token}`"));
+                };
+                let regex = Regex::new(&pattern).map_err(|e| e.to_string())?;
+                return Ok(Expr::Match(path, regex, negated));
+            }
+        }
+        Ok(Expr::Exists(path))
+    }
+
+    /// Reads a field path like `req.headers.host`.
+    fn path(&mut self) -> Result<Vec<String>, String> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_alphanumeric() || "_.@$-".contains(c)))
+            .unwrap_or(self.rest.len());
+        let path = &self.rest[..end];
+        if path.is_empty() || path.split('.').any(str::is_empty) {
+            return Err(self.error("a field"));
+        }
+        self.rest = &self.rest[end..];
+        Ok(path.split('.').map(str::to_string).collect())
+    }
+
+    /// Reads a quoted string, or a number, `true`, `false`, `null` or other
+    /// word.
+    fn value(&mut self) -> Result<Value, String> {
+        self.rest = self.rest.trim_start();
+        if let Some(quote) = self.rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            return self.quoted(quote).map(Value::String);
+        }
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || "()&|!<>=".contains(c))
+            .unwrap_or(self.rest.len());
+        let word = &self.rest[..end];
+        if word.is_empty() {
+            return Err(self.error("a value"));
+        }
+        self.rest = &self.rest[end..];
+        Ok(match word {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            "null" => Value::Null,
+            _ => serde_json::from_str::<Number>(word)
+                .map(Value::Number)
+                .unwrap_or_else(|_| Value::String(word.to_string())),
+        })
+    }
+
+    /// Reads a string in the given quotes, handling backslash escapes.
+    fn quoted(&mut self, quote: char) -> Result<String, String> {
+        let mut value = String::new();
+        let mut chars = self.rest.char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    // other escapes are kept for regexes, like `\d`
+                    Some((_, c)) if c == quote || c == '\\' => value.push(c),
+                    Some((_, c)) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        Err("unterminated quoted string".to_string())
+    }
+
+    /// Skips the token if it is next, and returns true if it was.
+    fn eat(&mut self, token: &str) -> bool {
+        let rest = self.rest.trim_start();
+        match rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.rest.trim_start() {
+            "" => format!("expected {expected} at the end"),
+            rest => format!("expected {expected} at `{rest}`"),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn entry() -> LogEntry {
+        let mut entry = LogEntry::new("2024-03-15T12:00:00Z", "warn", "Request failed");
+        entry.label = Some("web-1".into());
+        let extras = json!({
+            "status": 503,
+            "user_id": "42",
+            "path": "/api/users",
+            "cached": false,
+            "parent": null,
+            "req": {"headers": {"host": "example.com"}, "http.version": "1.1"},
+            "tags": ["a", "b"],
+            "http.method": "GET",
+            "trace_id": 9007199254740993u64,
+            "span_id": "-9007199254740993",
+        });
+        let serde_json::Value::Object(map) = extras else {
+            unreachable!()
+        };
+        entry.extras = map.into_iter().collect();
+        entry
+    }
+
+    fn matches(query: &str) -> bool {
+        query.parse::<Query>().unwrap().matches(&entry())
+    }
+
+    #[test]
+    fn compares_fields() {
+        for (query, expected) in [
+            ("status >= 500", true),
+            ("status < 500", false),
+            ("status == 503.0", true),
+            ("status == \"503\"", true),
+            ("user_id == 42", true),
+            ("user_id > 7", true),
+            ("user_id > \"7\"", false),
+            ("cached == false", true),
+            ("parent == null", true),
+            ("level == warn", true),
+            ("message != 'Request failed'", false),
+            ("label == \"web-1\"", true),
+            ("timestamp >= \"2024-03-15\"", true),
+            ("missing != 1", false),
+            ("req > 1", false),
+            ("trace_id == 9007199254740993", true),
+            ("trace_id == 9007199254740992", false),
+            ("trace_id > 9007199254740992", true),
+            ("trace_id < 1e300", true),
+            ("span_id == -9007199254740993", true),
+            ("span_id < -9007199254740992", true),
+        ] {
+            assert_eq!(matches(query), expected, "{query}");
+        }
+    }
+
+    #[test]
+    fn finds_nested_fields() {
+        for (query, expected) in [
+            ("req.headers.host == example.com", true),
+            ("req.http.version == '1.1'", true),
+            ("http.method == GET", true),
+            ("tags.1 == b", true),
+            ("tags.2", false),
+            ("req.headers", true),
+            ("parent", false),
+            ("status.code", false),
+        ] {
+            assert_eq!(matches(query), expected, "{query}");
+        }
+    }
+
+    #[test]
+    fn combines_conditions() {
+        for (query, expected) in [
+            (r#"status >= 500 && user_id == 42 && path =~ "^/api""#, true),
+            (r#"path !~ "^/api" || status == 503"#, true),
+            ("status == 200 || status == 404 && user_id == 42", false),
+            ("(status == 200 || status == 503) && user_id == 42", true),
+            ("!parent && !missing && cached", true),
+            ("!(status >= 500)", false),
+            (r#"message =~ '(?i)^request\s'"#, true),
+            (r"status =~ '^5\d\d$'", true),
+        ] {
+            assert_eq!(matches(query), expected, "{query}");
+        }
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        for (query, error) in [
+            ("", "expected a field at the end"),
+            ("status >=", "expected a value at the end"),
+            ("status = 1", "expected `&&` or `||` at `= 1`"),
+            ("(status", "expected `)` at the end"),
+            ("a && || b", "expected a field at `|| b`"),
+            ("path =~ 1", "expected a quoted regex after `=~`"),
+            ("path == 'open", "unterminated quoted string"),
+        ] {
+            assert_eq!(query.parse::<Query>().unwrap_err(), error, "{query}");
+        }
+        assert!("path =~ '('".parse::<Query>().is_err());
+    }
+}
@@ -0,0 +1,417 @@
+use std::{collections::HashMap, str::FromStr};
+
+use serde::de::Error as _;
+use serde_json::{Map, Value};
+
+use crate::{LogEntry, NumericLevels, SeverityLevel, Timestamp};
+
+// --------------------------------------------------------------------------
+
+/// One of the core fields every log entry has.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoreField {
+    Timestamp,
+    Level,
+    Message,
+}
+
+impl CoreField {
+    /// Returns the name of the core field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CoreField::Timestamp => "timestamp",
+            CoreField::Level => "level",
+            CoreField::Message => "message",
+        }
+    }
+}
+
+impl FromStr for CoreField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timestamp" => Ok(CoreField::Timestamp),
+            "level" => Ok(CoreField::Level),
+            "message" => Ok(CoreField::Message),
+            other => Err(format!(
+                "unknown field '{other}', expected one of: timestamp, level, message"
+            )),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+
+/// Describes which keys of a json object hold the core fields of a log entry.
+///
+/// Each core field has a list of candidate keys, which are tried in order.
+/// The first key present in the object is used for the core field, all other
+/// keys (including the candidates that weren't picked) end up in the extras.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::*;
+///
+/// let mut schema = FieldSchema::default();
+/// schema.set_candidates(CoreField::Level, ["severity"]);
+///
+/// let json = r#"{"ts": "2024-03-15T12:34:56.123Z", "severity": "warn", "msg": "Hi"}"#;
+/// let entry = schema.entry_from_map(serde_json::from_str(json).unwrap()).unwrap();
+/// assert_eq!(entry.level(), SeverityLevel::Warn);
+/// assert_eq!(entry.message, "Hi");
+/// assert!(entry.extras.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FieldSchema {
+    /// Candidate keys for the timestamp
+    pub timestamp: Vec<String>,
+    /// Candidate keys for the severity level
+    pub level: Vec<String>,
+    /// Candidate keys for the message
+    pub message: Vec<String>,
+    /// Level values with their severity, checked before the default mapping
+    pub levels: HashMap<String, SeverityLevel>,
+    /// The scale used for numeric levels
+    pub numeric_levels: NumericLevels,
+    /// The level to use for entries without one
+    pub default_level: Option<String>,
+    /// If true, an entry missing a core field is an error, otherwise the
+    /// missing field is left empty
+    pub strict: bool,
+    /// Candidate keys for a label shown in front of the entry, like the name
+    /// of the service that logged it
+    pub label: Vec<String>,
+    /// Keys starting with one of these prefixes are left out of the extras
+    pub hidden_prefixes: Vec<String>,
+    /// If set, a message that is itself a json object is read with this
+    /// schema, and its fields take the place of the outer ones
+    pub message_schema: Option<Box<FieldSchema>>,
+}
+
+impl Default for FieldSchema {
+    fn default() -> Self {
+        Self {
+            timestamp: to_strings(["timestamp", "time", "ts", "@timestamp"]),
+            level: to_strings(["level", "severity", "lvl"]),
+            message: to_strings(["message", "msg"]),
+            levels: HashMap::new(),
+            numeric_levels: NumericLevels::Auto,
+            default_level: None,
+            strict: false,
+            label: Vec::new(),
+            hidden_prefixes: Vec::new(),
+            message_schema: None,
+        }
+    }
+}
+
+impl FieldSchema {
+    /// Returns the candidate keys for the given core field.
+    pub fn candidates(&self, field: CoreField) -> &[String] {
+        match field {
+            CoreField::Timestamp => &self.timestamp,
+            CoreField::Level => &self.level,
+            CoreField::Message => &self.message,
+        }
+    }
+
+    /// Replaces the candidate keys for the given core field.
+    pub fn set_candidates<S: Into<String>>(
+        &mut self,
+        field: CoreField,
+        keys: impl IntoIterator<Item = S>,
+    ) {
+        let keys = keys.into_iter().map(Into::into).collect();
+        match field {
+            CoreField::Timestamp => self.timestamp = keys,
+            CoreField::Level => self.level = keys,
+            CoreField::Message => self.message = keys,
+        }
+    }
+
+    /// Applies a mapping of the form `field=key1,key2,...`, replacing the
+    /// candidate keys of the named core field.
+    pub fn apply_mapping(&mut self, mapping: &str) -> Result<(), String> {
+        let (field, keys) = mapping
+            .split_once('=')
+            .ok_or_else(|| format!("invalid field mapping '{mapping}', expected FIELD=KEY,..."))?;
+        let field = field.trim().parse::<CoreField>()?;
+        let keys: Vec<&str> = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .collect();
+        if keys.is_empty() {
+            return Err(format!("no keys given in field mapping '{mapping}'"));
+        }
+        self.set_candidates(field, keys);
+        Ok(())
+    }
+
+    /// Builds a log entry from a json object, taking the core fields from the
+    /// first matching candidate keys and moving everything else to the
+    /// extras.
+    ///
+    /// Missing core fields are an error in strict mode, otherwise they are
+    /// left empty.
+    pub fn entry_from_map(
+        &self,
+        mut map: Map<String, Value>,
+    ) -> Result<LogEntry, serde_json::Error> {
+        let timestamp = take_field(&mut map, &self.timestamp);
+        let timestamp = self.require(timestamp, CoreField::Timestamp)?;
+        let level = take_field(&mut map, &self.level).or_else(|| self.default_level.clone());
+        let level = self.require(level, CoreField::Level)?;
+        let message = take_field(&mut map, &self.message);
+        let message = self.require(message, CoreField::Message)?;
+        let label = take_field(&mut map, &self.label);
+        let mut entry = LogEntry {
+            timestamp: timestamp.map(Timestamp::parse),
+            severity: match &level {
+                Some(level) => self.severity_of(level),
+                None => SeverityLevel::Other(String::new()),
+            },
+            level,
+            message: message.unwrap_or_default(),
+            extras: map
+                .into_iter()
+                .filter(|(key, _)| !self.hidden_prefixes.iter().any(|p| key.starts_with(p)))
+                .collect(),
+            label,
+        };
+        if let Some(inner) = self.json_message(&entry.message)? {
+            entry.message = inner.message;
+            if inner.timestamp.is_some() {
+                entry.timestamp = inner.timestamp;
+            }
+            if inner.level.is_some() {
+                entry.level = inner.level;
+                entry.severity = inner.severity;
+            }
+            entry.extras.extend(inner.extras);
+        }
+        Ok(entry)
+    }
+
+    /// Reads a message that is itself a json object with the message schema,
+    /// if there is one.
+    fn json_message(&self, message: &str) -> Result<Option<LogEntry>, serde_json::Error> {
+        let Some(schema) = &self.message_schema else {
+            return Ok(None);
+        };
+        if !message.trim_start().starts_with('{') {
+            return Ok(None);
+        }
+        match serde_json::from_str(message) {
+            Ok(map) => schema.entry_from_map(map).map(Some),
+            // not json after all, so it is shown as written
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Checks that a core field is present when in strict mode.
+    fn require(
+        &self,
+        value: Option<String>,
+        field: CoreField,
+    ) -> Result<Option<String>, serde_json::Error> {
+        if self.strict && value.is_none() {
+            return Err(serde_json::Error::custom(format!(
+                "missing field `{}` (tried {})",
+                field.name(),
+                self.candidates(field)
+                    .iter()
+                    .map(|k| format!("`{k}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        Ok(value)
+    }
+
+    /// Applies a level mapping of the form `value=level`, e.g. `35=warn`,
+    /// where level is one of the npm level names.
+    pub fn apply_level_mapping(&mut self, mapping: &str) -> Result<(), String> {
+        let (value, level) = mapping
+            .split_once('=')
+            .ok_or_else(|| format!("invalid level mapping '{mapping}', expected VALUE=LEVEL"))?;
+        let severity = SeverityLevel::from_name(level.trim());
+        if let SeverityLevel::Other(_) = severity {
+            return Err(format!(
+                "unknown level '{}', expected one of: fatal, error, warn, info, debug, verbose",
+                level.trim()
+            ));
+        }
+        self.levels.insert(value.trim().to_string(), severity);
+        Ok(())
+    }
+
+    /// Returns the severity for a level value, using the level table of the
+    /// schema first and the default mapping second.
+    pub fn severity_of(&self, level: &str) -> SeverityLevel {
+        self.levels
+            .get(level)
+            .cloned()
+            .unwrap_or_else(|| SeverityLevel::parse(level, self.numeric_levels))
+    }
+}
+
+/// Removes the first candidate key with a non-null value from the map and
+/// returns its value as a string.
+fn take_field(map: &mut Map<String, Value>, candidates: &[String]) -> Option<String> {
+    candidates
+        .iter()
+        .find_map(|key| map.remove(key).filter(|v| !v.is_null()))
+        .map(value_to_string)
+}
+
+/// Converts a json value to a string, without quotes for string values.
+fn value_to_string(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+fn to_strings<const N: usize>(keys: [&str; N]) -> Vec<String> {
+    keys.into_iter().map(String::from).collect()
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(schema: &FieldSchema, json: &str) -> Result<LogEntry, serde_json::Error> {
+        schema.entry_from_map(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn default_schema_accepts_common_keys() {
+        let schema = FieldSchema::default();
+        let entry = parse(
+            &schema,
+            r#"{"ts": "2024-03-15T12:34:56.123Z", "lvl": "error", "msg": "Oops", "@timestamp": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            entry.timestamp.as_ref().map(Timestamp::as_str),
+            Some("2024-03-15T12:34:56.123Z")
+        );
+        assert_eq!(entry.level.as_deref(), Some("error"));
+        assert_eq!(entry.message, "Oops");
+        // '@timestamp' is a candidate, but loses to 'ts', so it is an extra
+        assert_eq!(entry.extras.len(), 1);
+        assert_eq!(entry.extras.get("@timestamp").unwrap(), &Value::from(1));
+    }
+
+    #[test]
+    fn candidates_are_tried_in_order() {
+        let mut schema = FieldSchema::default();
+        schema.set_candidates(CoreField::Message, ["text", "msg"]);
+        let entry = parse(
+            &schema,
+            r#"{"timestamp": "t", "level": "info", "msg": "second", "text": "first"}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.message, "first");
+        assert_eq!(entry.extras.get("msg").unwrap(), &Value::from("second"));
+    }
+
+    #[test]
+    fn missing_fields_are_errors_in_strict_mode() {
+        let schema = FieldSchema {
+            strict: true,
+            ..Default::default()
+        };
+        let err = parse(&schema, r#"{"timestamp": "t", "message": "m"}"#).unwrap_err();
+        assert!(err.to_string().starts_with("missing field `level`"));
+    }
+
+    #[test]
+    fn missing_fields_are_left_empty() {
+        let schema = FieldSchema::default();
+        let entry = parse(&schema, r#"{"level": null, "status": 200}"#).unwrap();
+        assert_eq!(entry.timestamp, None);
+        assert_eq!(entry.level, None);
+        assert_eq!(entry.level(), SeverityLevel::Other(String::new()));
+        assert_eq!(entry.message, "");
+        assert_eq!(entry.extras.len(), 1);
+    }
+
+    #[test]
+    fn non_string_values_are_converted() {
+        let schema = FieldSchema::default();
+        let entry = parse(&schema, r#"{"time": 1710506096, "level": 30, "msg": "m"}"#).unwrap();
+        assert_eq!(
+            entry.timestamp.as_ref().map(Timestamp::as_str),
+            Some("1710506096")
+        );
+        assert_eq!(entry.level.as_deref(), Some("30"));
+    }
+
+    #[test]
+    fn level_table_is_checked_first() {
+        let mut schema = FieldSchema::default();
+        schema.levels.insert("30".into(), SeverityLevel::Info);
+        schema.levels.insert("info".into(), SeverityLevel::Warn);
+        let entry = parse(&schema, r#"{"time": "t", "level": 30, "msg": "m"}"#).unwrap();
+        assert_eq!(entry.level(), SeverityLevel::Info);
+        let entry = parse(&schema, r#"{"time": "t", "level": "info", "msg": "m"}"#).unwrap();
+        assert_eq!(entry.level(), SeverityLevel::Warn);
+        let entry = parse(&schema, r#"{"time": "t", "level": "error", "msg": "m"}"#).unwrap();
+        assert_eq!(entry.level(), SeverityLevel::Error);
+    }
+
+    #[test]
+    fn default_level_is_used_when_missing() {
+        let schema = FieldSchema {
+            default_level: Some("info".into()),
+            ..Default::default()
+        };
+        let entry = parse(&schema, r#"{"time": "t", "msg": "m"}"#).unwrap();
+        assert_eq!(entry.level.as_deref(), Some("info"));
+        assert_eq!(entry.level(), SeverityLevel::Info);
+    }
+
+    #[test]
+    fn can_apply_level_mappings() {
+        let mut schema = FieldSchema::default();
+        schema.apply_level_mapping("35=Warn").unwrap();
+        schema.apply_level_mapping("audit = info").unwrap();
+        assert_eq!(schema.severity_of("35"), SeverityLevel::Warn);
+        assert_eq!(schema.severity_of("audit"), SeverityLevel::Info);
+        assert_eq!(schema.severity_of("30"), SeverityLevel::Info);
+        assert!(schema.apply_level_mapping("35").is_err());
+        assert!(schema.apply_level_mapping("35=loud").is_err());
+    }
+
+    #[test]
+    fn can_apply_mappings() {
+        let mut schema = FieldSchema::default();
+        schema.apply_mapping("level = severity, prio").unwrap();
+        assert_eq!(schema.level, vec!["severity", "prio"]);
+        assert!(schema.apply_mapping("level").is_err());
+        assert!(schema.apply_mapping("lvl=x").is_err());
+        assert!(schema.apply_mapping("level=").is_err());
+    }
+
+    #[test]
+    fn hidden_prefixes_are_left_out_of_extras() {
+        let schema = FieldSchema {
+            label: to_strings(["service"]),
+            hidden_prefixes: to_strings(["_"]),
+            ..Default::default()
+        };
+        let entry = parse(
+            &schema,
+            r#"{"level": "info", "service": "api", "_pid": 1, "__cursor": "c", "user": 7}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.label.as_deref(), Some("api"));
+        assert_eq!(entry.extras.len(), 1);
+        assert!(entry.extras.contains_key("user"));
+    }
+}
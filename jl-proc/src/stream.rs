@@ -0,0 +1,402 @@
+use std::io::{BufRead, Read};
+
+use serde::de::Error as _;
+use serde_json::{Map, Value};
+
+use crate::{FieldSchema, LineItem};
+
+// --------------------------------------------------------------------------
+
+/// An iterator over log entries from json objects that may span several
+/// lines, like pretty-printed objects one after another or wrapped in a
+/// top-level array.
+///
+/// The line number of each item is the line the object starts on. Text
+/// between the objects is returned line by line as [`LineItem::Text`], and
+/// other values in a top-level array one by one. After
+/// a parse error, the rest of the broken object is skipped up to the next
+/// line starting with `{` or `[` that is indented less than the object, and
+/// iteration stops after a read error.
+///
+/// ### Examples
+/// ```
+/// use std::io::BufReader;
+/// use jl_proc::{JsonStreamIterator, LineItem};
+///
+/// let input = r#"[
+///   {"level": "info", "message": "first"},
+///   {
+///     "level": "warn",
+///     "message": "second"
+///   }
+/// ]"#;
+/// let entries: Vec<_> = JsonStreamIterator::from_buf_reader(BufReader::new(input.as_bytes()))
+///     .collect();
+/// assert_eq!(entries.len(), 2);
+/// assert!(matches!(&entries[1], LineItem::Entry(entry) if entry.message == "second"));
+/// ```
+pub struct JsonStreamIterator<B: BufRead> {
+    reader: LineCounter<B>,
+    schema: FieldSchema,
+    in_array: bool,
+    is_error: bool,
+}
+
+impl<B: BufRead> JsonStreamIterator<B> {
+    pub fn from_buf_reader(reader: B) -> Self {
+        Self::with_schema(reader, FieldSchema::default())
+    }
+
+    /// Creates a new `JsonStreamIterator` that picks the core fields of each
+    /// entry according to the given schema.
+    pub fn with_schema(reader: B, schema: FieldSchema) -> Self {
+        Self {
+            reader: LineCounter {
+                inner: reader,
+                line_no: 1,
+                column: 1,
+            },
+            schema,
+            in_array: false,
+            is_error: false,
+        }
+    }
+
+    /// Skips whitespace, and the brackets and commas of a top-level array,
+    /// returning the first byte of the next value.
+    fn skip_to_value(&mut self) -> std::io::Result<Option<u8>> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            let Some(&byte) = buf.first() else {
+                return Ok(None);
+            };
+            let skipped = match byte {
+                b'[' if !self.in_array => {
+                    self.in_array = true;
+                    1
+                }
+                b',' if self.in_array => 1,
+                b']' if self.in_array => {
+                    self.in_array = false;
+                    1
+                }
+                b if b.is_ascii_whitespace() => {
+                    buf.iter().take_while(|b| b.is_ascii_whitespace()).count()
+                }
+                b => return Ok(Some(b)),
+            };
+            self.reader.consume(skipped);
+        }
+    }
+
+    /// Skips the rest of an object with a parse error that started at the
+    /// column, up to the next line that starts a value less indented than
+    /// it, which is where the next top-level value likely starts.
+    fn skip_broken_object(&mut self, column: usize) -> std::io::Result<()> {
+        self.read_rest_of_line()?;
+        loop {
+            let buf = self.reader.fill_buf()?;
+            let indent = buf.iter().take_while(|&&b| b == b' ' || b == b'\t').count();
+            match buf.get(indent) {
+                None => return Ok(()),
+                Some(b'{' | b'[') if indent < column => return Ok(()),
+                Some(_) => self.read_rest_of_line()?,
+            };
+        }
+    }
+
+    /// Reads a value in a top-level array that isn't an object, up to the
+    /// comma or bracket after it.
+    fn read_array_value(&mut self) -> std::io::Result<String> {
+        let mut value = Vec::new();
+        let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let end = buf.iter().position(|&b| {
+                if in_string {
+                    match b {
+                        _ if escaped => escaped = false,
+                        b'\\' => escaped = true,
+                        b'"' => in_string = false,
+                        _ => {}
+                    }
+                    return false;
+                }
+                match b {
+                    b'"' => in_string = true,
+                    b'[' | b'{' => depth += 1,
+                    b',' | b']' if depth == 0 => return true,
+                    b']' | b'}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                false
+            });
+            let used = end.unwrap_or(buf.len());
+            value.extend_from_slice(&buf[..used]);
+            self.reader.consume(used);
+            if end.is_some() {
+                break;
+            }
+        }
+        Ok(String::from_utf8_lossy(value.trim_ascii()).into_owned())
+    }
+
+    /// Reads the rest of the current line, without the line ending.
+    fn read_rest_of_line(&mut self) -> std::io::Result<String> {
+        let mut buf = Vec::new();
+        self.reader.read_until(b'\n', &mut buf)?;
+        while let Some(b'\n' | b'\r') = buf.last() {
+            buf.pop();
+        }
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    fn read_item(&mut self) -> std::io::Result<Option<LineItem>> {
+        let Some(first) = self.skip_to_value()? else {
+            return Ok(None);
+        };
+        let (line_no, column) = (self.reader.line_no, self.reader.column);
+        if first != b'{' {
+            let text = if self.in_array {
+                self.read_array_value()?
+            } else {
+                self.read_rest_of_line()?
+            };
+            return Ok(Some(LineItem::Text(line_no, text)));
+        }
+        let result = serde_json::Deserializer::from_reader(&mut self.reader)
+            .into_iter::<Map<String, Value>>()
+            .next();
+        Ok(match result {
+            Some(Ok(map)) => Some(match self.schema.entry_from_map(map) {
+                Ok(entry) => LineItem::Entry(entry),
+                Err(e) => LineItem::ParseError(line_no, e),
+            }),
+            Some(Err(e)) if e.is_io() => return Err(std::io::Error::from(e)),
+            Some(Err(e)) => {
+                self.skip_broken_object(column)?;
+                Some(LineItem::ParseError(
+                    line_no,
+                    reposition(e, line_no, column),
+                ))
+            }
+            None => None,
+        })
+    }
+}
+
+impl<B: BufRead> Iterator for JsonStreamIterator<B> {
+    type Item = LineItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_error {
+            return None;
+        }
+        match self.read_item() {
+            Ok(item) => item,
+            Err(e) => {
+                self.is_error = true;
+                Some(LineItem::ReadError(self.reader.line_no, e))
+            }
+        }
+    }
+}
+
+/// Moves the position of a parse error from being relative to the start of
+/// the object to being relative to the start of the input.
+fn reposition(error: serde_json::Error, line_no: usize, column: usize) -> serde_json::Error {
+    if error.line() == 0 {
+        return error;
+    }
+    let message = error.to_string();
+    let message = message
+        .strip_suffix(&format!(
+            " at line {} column {}",
+            error.line(),
+            error.column()
+        ))
+        .unwrap_or(&message);
+    let column = match error.line() {
+        1 => error.column() + column - 1,
+        _ => error.column(),
+    };
+    serde_json::Error::custom(format!(
+        "{message} at line {} column {column}",
+        error.line() + line_no - 1
+    ))
+}
+
+/// A reader that keeps track of the line and column of the next byte.
+struct LineCounter<B> {
+    inner: B,
+    line_no: usize,
+    column: usize,
+}
+
+/// Moves a line and column past the bytes.
+fn count(line_no: &mut usize, column: &mut usize, bytes: &[u8]) {
+    match bytes.iter().rposition(|&b| b == b'\n') {
+        Some(last) => {
+            *line_no += bytes.iter().filter(|&&b| b == b'\n').count();
+            *column = bytes.len() - last;
+        }
+        None => *column += bytes.len(),
+    }
+}
+
+impl<B: BufRead> Read for LineCounter<B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        count(&mut self.line_no, &mut self.column, &buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<B: BufRead> BufRead for LineCounter<B> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        // the buffer is already filled, so this doesn't read
+        if let Ok(buf) = self.inner.fill_buf() {
+            let consumed = &buf[..amount.min(buf.len())];
+            count(&mut self.line_no, &mut self.column, consumed);
+        }
+        self.inner.consume(amount);
+    }
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+
+    fn parse(raw: &str) -> Vec<LineItem> {
+        JsonStreamIterator::from_buf_reader(BufReader::new(raw.as_bytes())).collect()
+    }
+
+    #[test]
+    fn reads_concatenated_objects() {
+        let raw = r#"{
+  "level": "info",
+  "message": "first"
+}
+{"level": "info", "message": "second"}{"level": "info", "message": "third"}
+
+{
+  "level": "warn",
+  "message": "fourth",
+  "nested": {"a": [1, 2]}
+}
+"#;
+        let entries = parse(raw);
+        assert_eq!(entries.len(), 4);
+        let messages: Vec<_> = entries
+            .iter()
+            .map(|item| match item {
+                LineItem::Entry(entry) => entry.message.as_str(),
+                _ => panic!("expected an entry"),
+            })
+            .collect();
+        assert_eq!(messages, ["first", "second", "third", "fourth"]);
+    }
+
+    #[test]
+    fn reads_array_wrapped_objects() {
+        let raw = "[\n  {\"message\": \"first\"},\n  {\"message\": \"second\"}\n]\n[{\"message\": \"third\"}]";
+        let entries = parse(raw);
+        assert_eq!(entries.len(), 3);
+        assert!(
+            entries
+                .iter()
+                .all(|item| matches!(item, LineItem::Entry(_)))
+        );
+    }
+
+    #[test]
+    fn reports_starting_lines_of_errors() {
+        let raw = r#"{"message": "first"}
+not json
+{
+  "message": "broken",
+  "level": info
+}
+{
+  "message": "last"
+}"#;
+        let entries = parse(raw);
+        assert_eq!(entries.len(), 4);
+        assert!(matches!(entries[0], LineItem::Entry(_)));
+        assert!(matches!(&entries[1], LineItem::Text(2, text) if text == "not json"));
+        let LineItem::ParseError(3, e) = &entries[2] else {
+            panic!("expected a parse error on line 3")
+        };
+        assert!(e.to_string().ends_with("at line 5 column 12"), "{e}");
+        assert!(matches!(&entries[3], LineItem::Entry(entry) if entry.message == "last"));
+    }
+
+    #[test]
+    fn skips_the_rest_of_broken_objects() {
+        let raw = r#"[
+  {
+    "message": "broken",
+    "level": info,
+    "context": {
+      "ids": [1, 2]
+    }
+  },
+  {"message": "next"}
+]
+{"message": "last"}"#;
+        let entries = parse(raw);
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(entries[0], LineItem::ParseError(2, _)));
+        assert!(matches!(&entries[1], LineItem::Entry(entry) if entry.message == "next"));
+        assert!(matches!(&entries[2], LineItem::Entry(entry) if entry.message == "last"));
+    }
+
+    #[test]
+    fn reads_other_values_in_arrays_one_by_one() {
+        let raw = r#"[1, {"message": "a"}, "x, ]", [2, [3]],
+  {"message": "b"}]
+[{"message": "c"}]"#;
+        let items = parse(raw);
+        let shown: Vec<_> = items
+            .iter()
+            .map(|item| match item {
+                LineItem::Entry(entry) => format!("entry {}", entry.message),
+                LineItem::Text(line_no, text) => format!("{line_no}: {text}"),
+                _ => panic!("expected an entry or text"),
+            })
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                "1: 1",
+                "entry a",
+                r#"1: "x, ]""#,
+                "1: [2, [3]]",
+                "entry b",
+                "entry c"
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_lines_and_columns() {
+        let (mut line_no, mut column) = (1, 1);
+        count(&mut line_no, &mut column, b"ab");
+        assert_eq!((line_no, column), (1, 3));
+        count(&mut line_no, &mut column, b"c\nd\nef");
+        assert_eq!((line_no, column), (3, 3));
+        count(&mut line_no, &mut column, b"\n");
+        assert_eq!((line_no, column), (4, 1));
+    }
+}
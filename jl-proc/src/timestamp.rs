@@ -0,0 +1,297 @@
+use std::fmt::Display;
+
+use jiff::{
+    SignedDuration, Zoned,
+    civil::Time,
+    fmt::temporal::Pieces,
+    tz::{Offset, TimeZone},
+};
+
+// --------------------------------------------------------------------------
+
+/// The timestamp of a log entry, as it appears in the log and as parsed.
+///
+/// RFC 3339 date-times with any fractional precision (up to nanoseconds) and
+/// offset are understood, as well as epoch seconds, milliseconds,
+/// microseconds or nanoseconds, given as numbers or strings. The unit of an
+/// epoch timestamp is picked from the number of digits before the decimal
+/// point, and numbers with fewer than 9 digits, which would be before 1973,
+/// aren't taken as epoch timestamps. Timestamps that can't be parsed are
+/// kept as-is, and shown as written.
+///
+/// ### Examples
+/// ```
+/// use jl_proc::Timestamp;
+///
+/// let ts = Timestamp::parse("2024-03-15T12:34:56.123456+01:00");
+/// assert_eq!(ts.time_short(), "12:34:56.123");
+/// assert_eq!(ts.date_time(), "2024-03-15 12:34:56.123+01:00");
+///
+/// let ts = Timestamp::parse("1710506096123");
+/// assert_eq!(ts.time_short(), "12:34:56.123");
+///
+/// let ts = Timestamp::parse("yesterday");
+/// assert_eq!(ts.instant(), None);
+/// assert_eq!(ts.time_short(), "yesterday");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timestamp {
+    raw: String,
+    parsed: Option<(jiff::Timestamp, Offset)>,
+}
+
+impl Timestamp {
+    /// Parses a timestamp, keeping the raw text if it can't be parsed.
+    pub fn parse(raw: impl Into<String>) -> Self {
+        let raw = raw.into();
+        let text = raw.trim();
+        let parsed = parse_epoch(text).or_else(|| parse_date_time(text));
+        Self { raw, parsed }
+    }
+
+    /// Returns the timestamp as written in the log.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Returns the parsed point in time, if the timestamp could be parsed.
+    pub fn instant(&self) -> Option<jiff::Timestamp> {
+        self.parsed.map(|(instant, _)| instant)
+    }
+
+    /// Returns the offset from UTC the timestamp was written in. Epoch
+    /// timestamps, and date-times without an offset, are in UTC.
+    pub fn offset(&self) -> Option<Offset> {
+        self.parsed.map(|(_, offset)| offset)
+    }
+
+    /// Returns the parsed timestamp in the offset it was written in.
+    pub fn to_zoned(&self) -> Option<Zoned> {
+        self.parsed
+            .map(|(instant, offset)| instant.to_zoned(TimeZone::fixed(offset)))
+    }
+
+    /// Returns the time of day as `HH:MM:SS.mmm`, or the raw text if the
+    /// timestamp couldn't be parsed.
+    pub fn time_short(&self) -> String {
+        match self.to_zoned() {
+            Some(zoned) => format_time_short(&zoned),
+            None => self.raw.clone(),
+        }
+    }
+
+    /// Returns the date and time as `YYYY-MM-DD HH:MM:SS.mmm` followed by
+    /// the offset, or the raw text if the timestamp couldn't be parsed.
+    pub fn date_time(&self) -> String {
+        match self.to_zoned() {
+            Some(zoned) => format_date_time(&zoned),
+            None => self.raw.clone(),
+        }
+    }
+}
+
+/// Formats the time of day as `HH:MM:SS.mmm`.
+pub(crate) fn format_time_short(zoned: &Zoned) -> String {
+    zoned.strftime("%H:%M:%S%.3f").to_string()
+}
+
+/// Formats the date and time as `YYYY-MM-DD HH:MM:SS.mmm` followed by the
+/// offset, using `Z` for UTC.
+pub(crate) fn format_date_time(zoned: &Zoned) -> String {
+    if zoned.offset() == Offset::UTC {
+        zoned.strftime("%Y-%m-%d %H:%M:%S%.3fZ").to_string()
+    } else {
+        zoned.strftime("%Y-%m-%d %H:%M:%S%.3f%:z").to_string()
+    }
+}
+
+/// Formats a duration as `+HH:MM:SS.mmm`.
+pub(crate) fn format_duration(duration: SignedDuration) -> String {
+    let sign = if duration.is_negative() { '-' } else { '+' };
+    let millis = duration.abs().as_millis();
+    format!(
+        "{sign}{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// The fewest digits an epoch timestamp has, so small numbers like counts
+/// and status codes aren't read as times in 1970.
+const MIN_EPOCH_DIGITS: usize = 9;
+
+/// Parses an epoch timestamp like `1710506096`, `1710506096.123` or
+/// `1710506096123456`.
+fn parse_epoch(text: &str) -> Option<(jiff::Timestamp, Offset)> {
+    let (int, frac) = text.split_once('.').unwrap_or((text, ""));
+    if int.len() < MIN_EPOCH_DIGITS
+        || int.len() > 20
+        || !int.bytes().all(|b| b.is_ascii_digit())
+        || !frac.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    // nanoseconds per unit, picked by the number of digits
+    let unit: i128 = match int.len() {
+        ..=11 => 1_000_000_000,
+        12..=14 => 1_000_000,
+        15..=17 => 1_000,
+        _ => 1,
+    };
+    let mut nanos = int.parse::<i128>().ok()? * unit;
+    // frac only contains ascii digits, so slicing it is safe
+    let frac = &frac[..frac.len().min(9)];
+    if !frac.is_empty() {
+        let digits = frac.len() as u32;
+        nanos += frac.parse::<i128>().ok()? * unit / 10i128.pow(digits);
+    }
+    jiff::Timestamp::from_nanosecond(nanos)
+        .ok()
+        .map(|instant| (instant, Offset::UTC))
+}
+
+/// Parses an RFC 3339 (or ISO 8601) date-time. Date-times without an offset
+/// are taken to be in UTC.
+fn parse_date_time(text: &str) -> Option<(jiff::Timestamp, Offset)> {
+    let pieces = Pieces::parse(text).ok()?;
+    let offset = pieces.to_numeric_offset().unwrap_or(Offset::UTC);
+    let date_time = pieces
+        .date()
+        .to_datetime(pieces.time().unwrap_or(Time::midnight()));
+    let instant = date_time
+        .to_zoned(TimeZone::fixed(offset))
+        .ok()?
+        .timestamp();
+    Some((instant, offset))
+}
+
+// --------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn parses_rfc3339_with_any_precision() {
+        for (raw, short) in [
+            ("2024-03-15T12:34:56Z", "12:34:56.000"),
+            ("2024-03-15T12:34:56.1Z", "12:34:56.100"),
+            ("2024-03-15T12:34:56.123Z", "12:34:56.123"),
+            ("2024-03-15T12:34:56.123456789Z", "12:34:56.123"),
+            ("2024-03-15T12:34:56.123-05:00", "12:34:56.123"),
+            ("2024-03-15 12:34:56.123+0530", "12:34:56.123"),
+        ] {
+            let ts = Timestamp::parse(raw);
+            assert!(ts.instant().is_some(), "{raw}");
+            assert_eq!(ts.time_short(), short, "{raw}");
+        }
+        let ts = Timestamp::parse("2024-03-15T12:34:56.123-05:00");
+        assert_eq!(
+            ts.instant(),
+            Some("2024-03-15T17:34:56.123Z".parse().unwrap())
+        );
+        assert_eq!(ts.date_time(), "2024-03-15 12:34:56.123-05:00");
+    }
+
+    #[test]
+    fn parses_epoch_timestamps() {
+        let expected: jiff::Timestamp = "2024-03-15T12:34:56Z".parse().unwrap();
+        for raw in [
+            "1710506096",
+            "1710506096000",
+            "1710506096000000",
+            "1710506096000000000",
+        ] {
+            assert_eq!(Timestamp::parse(raw).instant(), Some(expected), "{raw}");
+        }
+        let ts = Timestamp::parse("1710506096.25");
+        assert_eq!(ts.time_short(), "12:34:56.250");
+        let ts = Timestamp::parse("1710506096123.5");
+        assert_eq!(
+            ts.instant().unwrap().as_nanosecond() % 1_000_000_000,
+            123_500_000
+        );
+        assert_eq!(ts.offset(), Some(Offset::UTC));
+    }
+
+    #[test]
+    fn keeps_malformed_timestamps() {
+        for raw in [
+            "",
+            "t",
+            "12:34",
+            "2024-13-45T99:00:00Z",
+            "1710506096.x",
+            "10",
+            "200.5",
+            "12345678",
+            "ÆØÅ",
+        ] {
+            let ts = Timestamp::parse(raw);
+            assert_eq!(ts.instant(), None, "{raw}");
+            assert_eq!(ts.time_short(), raw);
+            assert_eq!(ts.date_time(), raw);
+        }
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(
+            format_duration(SignedDuration::from_millis(3_723_004)),
+            "+01:02:03.004"
+        );
+        assert_eq!(
+            format_duration(SignedDuration::from_millis(-1500)),
+            "-00:00:01.500"
+        );
+        assert_eq!(format_duration(SignedDuration::ZERO), "+00:00:00.000");
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics_on_any_input(raw in "\\PC*") {
+            let ts = Timestamp::parse(raw.as_str());
+            let _ = ts.time_short();
+            let _ = ts.date_time();
+        }
+
+        #[test]
+        fn never_panics_on_timestamp_like_input(
+            raw in "[0-9]{0,22}(\\.[0-9]{0,12})?|[0-9]{4}-[0-9]{2}-[0-9]{2}[T ][0-9]{2}:[0-9]{2}(:[0-9]{2}(\\.[0-9]{0,12})?)?(Z|[+-][0-9]{2}:?[0-9]{2})?"
+        ) {
+            let ts = Timestamp::parse(raw.as_str());
+            let _ = ts.time_short();
+            let _ = ts.date_time();
+        }
+
+        #[test]
+        fn round_trips_rfc3339(
+            seconds in 0i64..4_102_444_800,
+            nanos in 0i32..1_000_000_000,
+            offset_minutes in -14 * 60i32..=14 * 60,
+            precision in 0u8..=9,
+        ) {
+            let instant = jiff::Timestamp::new(seconds, nanos).unwrap();
+            let offset = Offset::from_seconds(offset_minutes * 60).unwrap();
+            let raw = instant
+                .to_zoned(TimeZone::fixed(offset))
+                .strftime(&format!("%Y-%m-%dT%H:%M:%S%.{precision}f%:z"))
+                .to_string();
+            let ts = Timestamp::parse(raw.as_str());
+            let truncated = nanos - nanos % 10i32.pow(9 - precision as u32);
+            prop_assert_eq!(ts.instant(), Some(jiff::Timestamp::new(seconds, truncated).unwrap()));
+            prop_assert_eq!(ts.offset(), Some(offset));
+        }
+    }
+}
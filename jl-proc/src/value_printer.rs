@@ -1,4 +1,5 @@
-use crate::ansi_color;
+use crate::{ansi_color, grep::Highlighter};
+use regex::Regex;
 use serde_json::Value;
 use std::io::{Result, Write};
 
@@ -33,6 +34,7 @@ pub struct ValuePrinter {
     null_color: &'static str,
     punctuation_color: &'static str,
     reset_color: &'static str,
+    highlighter: Option<Highlighter>,
 }
 
 impl ValuePrinter {
@@ -69,9 +71,16 @@ impl ValuePrinter {
             null_color,
             punctuation_color,
             reset_color,
+            highlighter: None,
         }
     }
 
+    /// Highlights the matches of a pattern in the values printed
+    pub fn with_highlight(mut self, regex: Regex) -> Self {
+        self.highlighter = Some(Highlighter::new(regex, self.config.use_color));
+        self
+    }
+
     /// Pretty print a JSON value to the given writer
     pub fn print<W: Write>(&self, writer: &mut W, value: &Value) -> Result<()> {
         self.print_value(writer, value, 0)
@@ -129,16 +138,24 @@ impl ValuePrinter {
     fn print_value<W: Write>(&self, writer: &mut W, value: &Value, indent: usize) -> Result<()> {
         match value {
             Value::Null => {
-                write!(writer, "{}null{}", self.null_color, self.reset_color)?;
+                write!(writer, "{}", self.null_color)?;
+                self.write_text(writer, "null")?;
+                write!(writer, "{}", self.reset_color)?;
             }
             Value::Bool(b) => {
-                write!(writer, "{}{}{}", self.boolean_color, b, self.reset_color)?;
+                write!(writer, "{}", self.boolean_color)?;
+                self.write_text(writer, &b.to_string())?;
+                write!(writer, "{}", self.reset_color)?;
             }
             Value::Number(n) => {
-                write!(writer, "{}{}{}", self.number_color, n, self.reset_color)?;
+                write!(writer, "{}", self.number_color)?;
+                self.write_text(writer, &n.to_string())?;
+                write!(writer, "{}", self.reset_color)?;
             }
             Value::String(s) => {
-                write!(writer, "{}\"{}\"{}", self.string_color, s, self.reset_color)?;
+                write!(writer, "{}\"", self.string_color)?;
+                self.write_text(writer, s)?;
+                write!(writer, "\"{}", self.reset_color)?;
             }
             Value::Array(arr) => {
                 self.print_array(writer, arr, indent)?;
@@ -239,6 +256,14 @@ impl ValuePrinter {
         Ok(())
     }
 
+    /// Write the text of a simple value, with highlighted matches
+    fn write_text<W: Write>(&self, writer: &mut W, text: &str) -> Result<()> {
+        match &self.highlighter {
+            Some(highlighter) => highlighter.write(writer, text),
+            None => write!(writer, "{text}"),
+        }
+    }
+
     /// Write indentation spaces
     fn write_indent<W: Write>(&self, writer: &mut W, level: usize) -> Result<()> {
         for _ in 0..(level * self.config.indent_size) {
@@ -260,7 +285,10 @@ impl ValuePrinter {
     }
 
     /// Determine if an object should be formatted compactly (on one line)
-    fn should_format_compact_object(&self, obj: &serde_json::Map<String, Value>) -> bool {
+    pub(crate) fn should_format_compact_object(
+        &self,
+        obj: &serde_json::Map<String, Value>,
+    ) -> bool {
         // Compact if empty or all values are simple and the total estimated length is reasonable
         if obj.is_empty() {
             return true;